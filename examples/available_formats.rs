@@ -0,0 +1,11 @@
+use libclipboard::{Clipboard, ClipboardError};
+
+fn main() -> Result<(), ClipboardError> {
+    let clipboard = Clipboard::new()?;
+
+    for format in clipboard.available_formats()? {
+        println!("{:?} ({:?})", format.kind, format.native);
+    }
+
+    Ok(())
+}
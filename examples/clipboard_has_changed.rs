@@ -1,15 +1,15 @@
 use std::{thread::sleep, time::Duration};
 
-use libclipboard::Clipboard;
+use libclipboard::{Clipboard, ClipboardError};
 
-fn main() -> Result<(), String> {
+fn main() -> Result<(), ClipboardError> {
     let clipboard = Clipboard::new()?;
 
     println!("Starting loop");
     loop {
         if clipboard.has_changed() {
             println!("Clipboard contents has changed to:");
-            println!("{:#?}", clipboard.get_item());
+            println!("{:#?}", clipboard.get_item()?);
         }
 
         sleep(Duration::from_millis(500));
@@ -1,16 +1,16 @@
-use libclipboard::{Clipboard, ClipboardItem};
+use libclipboard::{Clipboard, ClipboardError, ClipboardItem};
 
-fn main() -> Result<(), String> {
+fn main() -> Result<(), ClipboardError> {
     let mut clipboard = Clipboard::new()?;
 
-    clipboard.set_item(ClipboardItem::Text("Hello World!".to_owned()));
-    println!("{:?}", clipboard.get_item());
+    clipboard.set_item(ClipboardItem::Text("Hello World!".to_owned()))?;
+    println!("{:?}", clipboard.get_item()?);
 
-    clipboard.set_item(ClipboardItem::Text("Goodbye World!".to_owned()));
-    println!("{:?}", clipboard.get_item());
+    clipboard.set_item(ClipboardItem::Text("Goodbye World!".to_owned()))?;
+    println!("{:?}", clipboard.get_item()?);
 
-    clipboard.set_item(ClipboardItem::UnicodeText("Hi! 👋".to_string()));
-    println!("{:?}", clipboard.get_item());
+    clipboard.set_item(ClipboardItem::UnicodeText("Hi! 👋".to_string()))?;
+    println!("{:?}", clipboard.get_item()?);
 
     Ok(())
 }
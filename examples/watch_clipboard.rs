@@ -0,0 +1,14 @@
+use libclipboard::{Clipboard, ClipboardError};
+
+fn main() -> Result<(), ClipboardError> {
+    let clipboard = Clipboard::new()?;
+    let watcher = clipboard.watch()?;
+
+    println!("Watching the clipboard");
+    for change in watcher.subscribe() {
+        println!("Clipboard contents changed at {:?}:", change.timestamp);
+        println!("{:#?}", clipboard.get_item()?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{mpsc, Mutex},
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use futures::{
+    channel::{mpsc::UnboundedReceiver, oneshot},
+    Stream, StreamExt,
+};
+
+use crate::{
+    backend::BackendFactory,
+    error::ClipboardError,
+    expiry::ExpiryHandle,
+    formats::{FormatInfo, FormatKind},
+    models::{Clipboard, ClipboardItem, ClipboardKind},
+    options::{GetOptions, SetOptions, WriteOptions},
+    snapshot::ClipboardSnapshot,
+    watcher::{ClipboardChange, ClipboardWatcher},
+};
+
+type Request = Box<dyn FnOnce(&mut Clipboard) + Send>;
+
+/// A clipboard that can be used from async code without blocking the executor.
+///
+/// Every operation runs on a worker thread that owns the backend, so the futures returned here
+/// work with any executor.
+pub struct AsyncClipboard {
+    factory: BackendFactory,
+    requests: Option<mpsc::Sender<Request>>,
+    worker: Option<JoinHandle<()>>,
+    watcher: Mutex<Option<ClipboardWatcher>>,
+}
+
+impl AsyncClipboard {
+    pub fn new() -> Result<Self, ClipboardError> {
+        Self::with_kind(ClipboardKind::Clipboard)
+    }
+
+    /// Opens one of the system's clipboards or selections, as [`Clipboard::with_kind`] does.
+    pub fn with_kind(kind: ClipboardKind) -> Result<Self, ClipboardError> {
+        Clipboard::with_kind(kind)?.to_async()
+    }
+
+    /// Creates a clipboard whose worker thread uses a backend opened by `factory`.
+    pub fn from_factory(factory: BackendFactory) -> Result<Self, ClipboardError> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let (requests, receiver) = mpsc::channel::<Request>();
+
+        let worker = {
+            let factory = factory.clone();
+
+            thread::Builder::new()
+                .name("libclipboard-worker".to_owned())
+                .spawn(move || {
+                    let mut clipboard = match factory() {
+                        Ok(backend) => Clipboard::from(backend),
+                        Err(error) => {
+                            let _ = ready_tx.send(Err(error));
+                            return;
+                        }
+                    };
+
+                    let _ = ready_tx.send(Ok(()));
+
+                    // Runs until the `AsyncClipboard` drops its sender
+                    for request in receiver {
+                        request(&mut clipboard);
+                    }
+                })
+                .map_err(|error| ClipboardError::BackendUnavailable(error.to_string()))?
+        };
+
+        let clipboard = Self {
+            factory,
+            requests: Some(requests),
+            worker: Some(worker),
+            watcher: Mutex::new(None),
+        };
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(clipboard),
+            Ok(Err(error)) => Err(error),
+            Err(_) => Err(worker_stopped()),
+        }
+    }
+
+    pub async fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.run(|clipboard| clipboard.get_item()).await
+    }
+
+    pub async fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        self.run(|clipboard| clipboard.get_items()).await
+    }
+
+    /// See [`Clipboard::get_item_with_options`].
+    pub async fn get_item_with_options(
+        &self,
+    ) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        self.run(|clipboard| clipboard.get_item_with_options())
+            .await
+    }
+
+    /// See [`Clipboard::get_items_with_options`].
+    pub async fn get_items_with_options(
+        &self,
+    ) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        self.run(|clipboard| clipboard.get_items_with_options())
+            .await
+    }
+
+    pub async fn get_format(
+        &self,
+        kind: FormatKind,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.run(move |clipboard| clipboard.get_format(&kind)).await
+    }
+
+    /// See [`Clipboard::get_format_with_options`].
+    pub async fn get_format_with_options(
+        &self,
+        kind: FormatKind,
+        options: GetOptions,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.run(move |clipboard| clipboard.get_format_with_options(&kind, &options))
+            .await
+    }
+
+    /// See [`Clipboard::get_preferred`].
+    pub async fn get_preferred(
+        &self,
+        preferences: Vec<FormatKind>,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.run(move |clipboard| clipboard.get_preferred(&preferences))
+            .await
+    }
+
+    /// See [`Clipboard::get_preferred_with_options`].
+    pub async fn get_preferred_with_options(
+        &self,
+        preferences: Vec<FormatKind>,
+        options: GetOptions,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.run(move |clipboard| clipboard.get_preferred_with_options(&preferences, &options))
+            .await
+    }
+
+    pub async fn set_item(&self, item: ClipboardItem) -> Result<(), ClipboardError> {
+        self.run(move |clipboard| clipboard.set_item(item)).await
+    }
+
+    pub async fn set_items(&self, items: Vec<ClipboardItem>) -> Result<(), ClipboardError> {
+        self.run(move |clipboard| clipboard.set_items(&items)).await
+    }
+
+    /// See [`Clipboard::set_item_with_expiry`].
+    pub async fn set_item_with_expiry(
+        &self,
+        item: ClipboardItem,
+        timeout: Duration,
+    ) -> Result<ExpiryHandle, ClipboardError> {
+        self.run(move |clipboard| clipboard.set_item_with_expiry(item, timeout))
+            .await
+    }
+
+    /// See [`Clipboard::set_items_with_options`].
+    pub async fn set_items_with_options(
+        &self,
+        items: Vec<ClipboardItem>,
+        options: SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.run(move |clipboard| clipboard.set_items_with_options(&items, &options))
+            .await
+    }
+
+    /// See [`Clipboard::set_items_with_write_options`].
+    pub async fn set_items_with_write_options(
+        &self,
+        items: Vec<ClipboardItem>,
+        options: SetOptions,
+        write_options: WriteOptions,
+    ) -> Result<(), ClipboardError> {
+        self.run(move |clipboard| {
+            clipboard.set_items_with_write_options(&items, &options, &write_options)
+        })
+        .await
+    }
+
+    /// See [`Clipboard::options`].
+    pub async fn options(&self) -> Result<SetOptions, ClipboardError> {
+        self.run(|clipboard| clipboard.options()).await
+    }
+
+    /// See [`Clipboard::clear`].
+    pub async fn clear(&self) -> Result<bool, ClipboardError> {
+        self.run(|clipboard| clipboard.clear()).await
+    }
+
+    pub async fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        self.run(|clipboard| clipboard.available_formats()).await
+    }
+
+    /// See [`Clipboard::change_count`].
+    pub async fn change_count(&self) -> Result<u64, ClipboardError> {
+        self.run(|clipboard| clipboard.change_count()).await
+    }
+
+    pub async fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        self.run(|clipboard| clipboard.snapshot()).await
+    }
+
+    pub async fn restore(&self, snapshot: ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.run(move |clipboard| clipboard.restore(&snapshot))
+            .await
+    }
+
+    /// Returns a stream of every change made to the clipboard from now on.
+    ///
+    /// The changes are observed by a [`ClipboardWatcher`] that is started on the first call and
+    /// shared by every stream.
+    pub fn changes(&self) -> Result<ClipboardChanges, ClipboardError> {
+        let mut watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+
+        let watcher = match &mut *watcher {
+            Some(watcher) => watcher,
+            None => watcher.insert(ClipboardWatcher::new(self.factory.clone())?),
+        };
+
+        Ok(ClipboardChanges {
+            receiver: watcher.subscribe_unbounded(),
+        })
+    }
+
+    /// Sends `f` to the worker thread and waits for its result.
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Clipboard) -> Result<T, ClipboardError> + Send + 'static,
+    ) -> Result<T, ClipboardError> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.requests
+            .as_ref()
+            .ok_or_else(worker_stopped)?
+            .send(Box::new(move |clipboard| {
+                let _ = sender.send(f(clipboard));
+            }))
+            .map_err(|_| worker_stopped())?;
+
+        receiver.await.map_err(|_| worker_stopped())?
+    }
+}
+
+fn worker_stopped() -> ClipboardError {
+    ClipboardError::BackendUnavailable("the clipboard worker thread has stopped".to_owned())
+}
+
+impl Clipboard {
+    /// Returns an [`AsyncClipboard`] onto the same clipboard.
+    pub fn to_async(&self) -> Result<AsyncClipboard, ClipboardError> {
+        AsyncClipboard::from_factory(self.factory())
+    }
+}
+
+impl fmt::Debug for AsyncClipboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncClipboard")
+            .field("running", &self.worker.is_some())
+            .finish()
+    }
+}
+
+impl Drop for AsyncClipboard {
+    fn drop(&mut self) {
+        // Closing the channel makes the worker exit once the queued requests are done
+        self.requests.take();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A stream of the changes made to a clipboard, returned by [`AsyncClipboard::changes`].
+#[derive(Debug)]
+pub struct ClipboardChanges {
+    receiver: UnboundedReceiver<ClipboardChange>,
+}
+
+impl Stream for ClipboardChanges {
+    type Item = ClipboardChange;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
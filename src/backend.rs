@@ -0,0 +1,177 @@
+use std::{
+    fmt::Debug,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::ClipboardError,
+    formats::{FormatInfo, FormatKind},
+    models::{ClipboardItem, ClipboardKind},
+    options::SetOptions,
+    snapshot::ClipboardSnapshot,
+    watcher::NativeChange,
+};
+
+/// How often the default [`ClipboardBackend::wait_for_change`] checks for changes.
+const CHANGE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Number of times the default [`ClipboardBackend::get_items_with_options`] reads the clipboard
+/// before giving up on getting the items and options of the same contents.
+const CONSISTENT_READ_ATTEMPTS: usize = 3;
+
+/// Opens a new backend onto the same clipboard as the backend it was taken from.
+///
+/// Backends are not required to be `Send`, so code that needs the clipboard on another thread
+/// sends the factory there instead and opens its own backend.
+pub type BackendFactory =
+    Arc<dyn Fn() -> Result<Box<dyn ClipboardBackend>, ClipboardError> + Send + Sync>;
+
+/// A clipboard implementation that a [`Clipboard`](crate::Clipboard) can be built on top of.
+///
+/// The platform backends (`WindowsCC`, `MacOSCC`, `X11CC`, `WaylandCC`) implement this trait, and so can any
+/// user-provided type.
+pub trait ClipboardBackend: Debug {
+    /// Returns which clipboard or selection the backend reads and writes.
+    fn kind(&self) -> ClipboardKind {
+        ClipboardKind::Clipboard
+    }
+
+    /// Returns the first item on the clipboard that can be represented as a [`ClipboardItem`],
+    /// or `None` if there is no such item.
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError>;
+
+    /// Returns every item on the clipboard that can be represented as a [`ClipboardItem`].
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError>;
+
+    /// Returns the first item of the given kind, or `None` if the clipboard has no such item.
+    ///
+    /// Backends should override this to avoid reading every format on the clipboard.
+    fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        Ok(self
+            .get_items()?
+            .into_iter()
+            .find(|item| item.kind() == *kind))
+    }
+
+    /// Replaces the contents of the clipboard with `item`.
+    fn set_item(&mut self, item: ClipboardItem) -> Result<(), ClipboardError> {
+        self.set_items(&[item])
+    }
+
+    /// Replaces the contents of the clipboard with every item in `items`, written as
+    /// representations of the same content under a single ownership.
+    ///
+    /// Items are given in order of preference; if several items share a native format, the
+    /// first one is kept.
+    fn set_items(&mut self, items: &[ClipboardItem]) -> Result<(), ClipboardError> {
+        self.set_items_with_options(items, &SetOptions::default())
+    }
+
+    /// Like [`set_items`](ClipboardBackend::set_items), but also writes the markers for
+    /// `options`.
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError>;
+
+    /// Returns the options the current contents were written with, as far as the markers on
+    /// the clipboard tell.
+    fn options(&self) -> Result<SetOptions, ClipboardError>;
+
+    /// Like [`get_item`](ClipboardBackend::get_item), but also returns the options the item was
+    /// written with, both read from the same clipboard contents.
+    ///
+    /// The default implementation reads them one after the other and reads again if the
+    /// [`change_count`](ClipboardBackend::change_count) moved in between.
+    fn get_item_with_options(&self) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        read_consistently(self, || match self.get_item()? {
+            Some(item) => Ok(Some((item, self.options()?))),
+            None => Ok(None),
+        })
+    }
+
+    /// Like [`get_items`](ClipboardBackend::get_items), but also returns the options the items
+    /// were written with, both read from the same clipboard contents.
+    ///
+    /// The default implementation reads them one after the other and reads again if the
+    /// [`change_count`](ClipboardBackend::change_count) moved in between.
+    fn get_items_with_options(&self) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        read_consistently(self, || Ok((self.get_items()?, self.options()?)))
+    }
+
+    /// Empties the clipboard, returning `true` if its contents were written by this backend.
+    ///
+    /// Clearing counts as a change, both for this backend and for any other observer.
+    fn clear(&mut self) -> Result<bool, ClipboardError>;
+
+    /// Lists the formats currently on the clipboard, in the order the clipboard reports them.
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError>;
+
+    /// Captures the raw data of every format on the clipboard, including formats that can't be
+    /// represented as a [`ClipboardItem`].
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError>;
+
+    /// Replaces the contents of the clipboard with the formats in `snapshot`, taking ownership
+    /// of the clipboard.
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError>;
+
+    /// Returns a number that increases every time the clipboard contents change.
+    ///
+    /// Only differences between values are meaningful; the number is not the count of changes
+    /// made since any particular point in time.
+    fn change_count(&self) -> Result<u64, ClipboardError>;
+
+    /// Returns `true` if the clipboard has changed since the last call.
+    ///
+    /// Every backend tracks changes on its own, so calls on one backend don't affect another.
+    fn has_changed(&self) -> bool;
+
+    /// Blocks until [`has_changed`](ClipboardBackend::has_changed) would return `true` or
+    /// `timeout` elapses, returning whether the clipboard changed.
+    ///
+    /// The default implementation polls `has_changed`.
+    fn wait_for_change(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.has_changed() {
+                return Ok(true);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+
+            thread::sleep(remaining.min(CHANGE_POLL_INTERVAL));
+        }
+    }
+
+    /// Returns what the platform reported about the latest change, for backends that receive
+    /// change events.
+    fn last_native_change(&self) -> Option<NativeChange> {
+        None
+    }
+
+    /// Returns a factory that opens new backends onto the same clipboard.
+    fn factory(&self) -> BackendFactory;
+}
+
+/// Calls `read` until the change count of `backend` is the same before and after it.
+fn read_consistently<B: ClipboardBackend + ?Sized, T>(
+    backend: &B,
+    mut read: impl FnMut() -> Result<T, ClipboardError>,
+) -> Result<T, ClipboardError> {
+    for _ in 0..CONSISTENT_READ_ATTEMPTS {
+        let change_count = backend.change_count()?;
+        let value = read()?;
+
+        if backend.change_count()? == change_count {
+            return Ok(value);
+        }
+    }
+
+    Err(ClipboardError::ClipboardBusy)
+}
@@ -0,0 +1,443 @@
+use std::io::Cursor;
+
+use crate::{
+    error::ClipboardError,
+    formats::FormatKind,
+    image_data::{ImageData, ImageFormat},
+    models::ClipboardItem,
+};
+
+/// The kinds a missing kind can be synthesized from when reading, in order of preference.
+const READ_SOURCES: &[(FormatKind, &[FormatKind])] = &[
+    (
+        FormatKind::UnicodeText,
+        &[
+            FormatKind::Text,
+            FormatKind::Html,
+            FormatKind::Rtf,
+            FormatKind::Url,
+            FormatKind::FileList,
+        ],
+    ),
+    (
+        FormatKind::Text,
+        &[
+            FormatKind::UnicodeText,
+            FormatKind::Html,
+            FormatKind::Rtf,
+            FormatKind::Url,
+            FormatKind::FileList,
+        ],
+    ),
+    (FormatKind::Png, &[FormatKind::Image, FormatKind::Tiff]),
+    (FormatKind::Image, &[FormatKind::Png, FormatKind::Tiff]),
+    (FormatKind::Tiff, &[FormatKind::Image, FormatKind::Png]),
+];
+
+/// The representations added when writing: a kind, the kinds that already provide it, and the
+/// kinds it can be synthesized from.
+///
+/// Decoded images aren't a source for PNG, as they are written as PNG where the platform reads
+/// PNG.
+const WRITE_FALLBACKS: &[(FormatKind, &[FormatKind], &[FormatKind])] = &[
+    (
+        FormatKind::UnicodeText,
+        &[FormatKind::UnicodeText, FormatKind::Text],
+        &[
+            FormatKind::Html,
+            FormatKind::Rtf,
+            FormatKind::Url,
+            FormatKind::FileList,
+        ],
+    ),
+    (
+        FormatKind::Png,
+        &[FormatKind::Png, FormatKind::Image],
+        &[FormatKind::Tiff],
+    ),
+];
+
+impl ClipboardItem {
+    /// Converts the item into another kind, returning `None` if there is no conversion between
+    /// the two.
+    ///
+    /// Text can be extracted from HTML, RTF, URLs and file lists, and images can be converted
+    /// between PNG, TIFF and decoded pixels. Converting an item into its own kind clones it.
+    pub fn convert(&self, target: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        if self.kind() == *target {
+            return Ok(Some(self.clone()));
+        }
+
+        let text = match self {
+            Self::Text(text) | Self::UnicodeText(text) | Self::Url(text) | Self::FilePath(text) => {
+                Some(text.clone())
+            }
+            Self::Html(html) => Some(html_to_text(html)),
+            Self::Rtf(rtf) => Some(rtf_to_text(rtf)),
+            _ => None,
+        };
+
+        let image = || match self {
+            Self::Png(data) => ImageData::decode(data.get_ref(), ImageFormat::Png).map(Some),
+            Self::Tiff(data) => ImageData::decode(data.get_ref(), ImageFormat::Tiff).map(Some),
+            Self::Image(image) => Ok(Some(image.clone())),
+            _ => Ok(None),
+        };
+
+        Ok(match (target, text) {
+            (FormatKind::Text, Some(text)) => Some(Self::Text(text)),
+            (FormatKind::UnicodeText, Some(text)) => Some(Self::UnicodeText(text)),
+            (FormatKind::Png, _) => match image()? {
+                Some(image) => Some(Self::Png(Cursor::new(image.encode(ImageFormat::Png)?))),
+                None => None,
+            },
+            (FormatKind::Tiff, _) => match image()? {
+                Some(image) => Some(Self::Tiff(Cursor::new(image.encode(ImageFormat::Tiff)?))),
+                None => None,
+            },
+            (FormatKind::Image, _) => image()?.map(Self::Image),
+            _ => None,
+        })
+    }
+}
+
+/// Returns the kinds `target` can be synthesized from when reading.
+pub(crate) fn read_sources(target: &FormatKind) -> &'static [FormatKind] {
+    READ_SOURCES
+        .iter()
+        .find(|(kind, _)| kind == target)
+        .map_or(&[], |(_, sources)| sources)
+}
+
+/// Returns `items` followed by the plain text and PNG representations that they don't provide
+/// but can be synthesized from them.
+pub(crate) fn with_fallbacks(
+    items: &[ClipboardItem],
+) -> Result<Vec<ClipboardItem>, ClipboardError> {
+    let mut all = items.to_vec();
+
+    for (target, provided_by, sources) in WRITE_FALLBACKS {
+        if items.iter().any(|item| provided_by.contains(&item.kind())) {
+            continue;
+        }
+
+        let source = sources
+            .iter()
+            .find_map(|source| items.iter().find(|item| item.kind() == *source));
+
+        if let Some(fallback) = source.map(|item| item.convert(target)).transpose()? {
+            all.extend(fallback);
+        }
+    }
+
+    Ok(all)
+}
+
+/// Extracts the text of an HTML document or fragment, with a line break after each block.
+fn html_to_text(html: &str) -> String {
+    const BLOCKS: &[&str] = &[
+        "address",
+        "article",
+        "blockquote",
+        "dd",
+        "div",
+        "dl",
+        "dt",
+        "footer",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "header",
+        "hr",
+        "li",
+        "ol",
+        "p",
+        "pre",
+        "section",
+        "table",
+        "tr",
+        "ul",
+    ];
+    const HIDDEN: &[&str] = &["head", "script", "style", "template", "title"];
+
+    let mut text = String::new();
+    let mut hidden: Option<String> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        if let Some(tag) = rest.strip_prefix('<') {
+            let end = tag.find('>').unwrap_or(tag.len());
+            let (closing, name) = match tag[..end].strip_prefix('/') {
+                Some(name) => (true, name),
+                None => (false, &tag[..end]),
+            };
+            let name = name
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+
+            rest = tag.get(end + 1..).unwrap_or_default();
+
+            match &hidden {
+                Some(hidden_name) if closing && *hidden_name == name => hidden = None,
+                Some(_) => {}
+                None if !closing && HIDDEN.contains(&name.as_str()) => hidden = Some(name),
+                None if name == "br" => {
+                    trim_spaces(&mut text);
+                    text.push('\n');
+                }
+                None if BLOCKS.contains(&name.as_str()) => {
+                    trim_spaces(&mut text);
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                }
+                None => {}
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+
+        if hidden.is_none() {
+            let decoded = decode_entities(&rest[..end]);
+
+            let separated = !text.is_empty() && !text.ends_with(['\n', ' ']);
+
+            // Runs of whitespace collapse into a single space, as they do when rendered
+            if separated && decoded.starts_with(char::is_whitespace) {
+                text.push(' ');
+            }
+
+            for (i, word) in decoded.split_whitespace().enumerate() {
+                if i > 0 {
+                    text.push(' ');
+                }
+                text.push_str(word);
+            }
+
+            if decoded.ends_with(char::is_whitespace) && !text.ends_with(['\n', ' ']) {
+                text.push(' ');
+            }
+        }
+
+        rest = &rest[end..];
+    }
+
+    text.lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+fn trim_spaces(text: &mut String) {
+    let trimmed = text.trim_end_matches(' ').len();
+    text.truncate(trimmed);
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| (&rest[1..end + 1], end + 2));
+
+        let character = entity.and_then(|(name, _)| match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => {
+                let code = match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => name.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (character, entity) {
+            (Some(character), Some((_, len))) => {
+                decoded.push(character);
+                rest = &rest[len..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Extracts the text of an RTF document, skipping font tables, pictures and other metadata.
+fn rtf_to_text(rtf: &str) -> String {
+    const HIDDEN: &[&str] = &[
+        "colortbl",
+        "fonttbl",
+        "footer",
+        "header",
+        "info",
+        "listtable",
+        "pict",
+        "stylesheet",
+    ];
+
+    struct Group {
+        hidden: bool,
+        /// Number of characters that follow a `\u` control word as a fallback for old readers.
+        unicode_skip: usize,
+    }
+
+    let mut text = String::new();
+    let mut groups = vec![Group {
+        hidden: false,
+        unicode_skip: 1,
+    }];
+    let mut skip = 0;
+    // The first half of a code point above U+FFFF, which RTF writes as two `\u` control words
+    let mut high_surrogate = None;
+    let mut chars = rtf.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let group = groups
+            .last_mut()
+            .expect("the outermost group is never popped");
+
+        match c {
+            '{' => {
+                let hidden = group.hidden;
+                let unicode_skip = group.unicode_skip;
+                groups.push(Group {
+                    hidden,
+                    unicode_skip,
+                });
+            }
+            '}' => {
+                if groups.len() > 1 {
+                    groups.pop();
+                }
+            }
+            '\\' => match chars.peek().copied() {
+                Some('\\' | '{' | '}') => {
+                    let c = chars.next().unwrap_or_default();
+                    push_rtf_char(&mut text, group.hidden, &mut skip, c);
+                }
+                Some('~') => {
+                    chars.next();
+                    push_rtf_char(&mut text, group.hidden, &mut skip, '\u{a0}');
+                }
+                Some('*') => {
+                    chars.next();
+                    group.hidden = true;
+                }
+                Some('\'') => {
+                    chars.next();
+                    let hex: String = chars.by_ref().take(2).collect();
+
+                    if let Ok(byte) = u8::from_str_radix(&hex, 16) {
+                        push_rtf_char(&mut text, group.hidden, &mut skip, cp1252_char(byte));
+                    }
+                }
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut word = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                        word.push(c);
+                    }
+
+                    let mut parameter = String::new();
+                    if let Some(c) = chars.next_if_eq(&'-') {
+                        parameter.push(c);
+                    }
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        parameter.push(c);
+                    }
+                    let parameter = parameter.parse::<i32>().ok();
+
+                    // A space ends the control word and isn't part of the text
+                    chars.next_if_eq(&' ');
+                    skip = 0;
+
+                    match word.as_str() {
+                        word if HIDDEN.contains(&word) => group.hidden = true,
+                        "par" | "line" | "row" => {
+                            push_rtf_char(&mut text, group.hidden, &mut 0, '\n')
+                        }
+                        "tab" | "cell" => push_rtf_char(&mut text, group.hidden, &mut 0, '\t'),
+                        "uc" => group.unicode_skip = parameter.unwrap_or(1).max(0) as usize,
+                        "u" => {
+                            // Negative values are how RTF writes code points above 0x7fff
+                            let code = parameter.unwrap_or_default() as i16 as u16 as u32;
+                            let code = match (high_surrogate.take(), code) {
+                                (_, 0xd800..=0xdbff) => {
+                                    high_surrogate = Some(code);
+                                    None
+                                }
+                                (Some(high), 0xdc00..=0xdfff) => {
+                                    Some(0x10000 + ((high - 0xd800) << 10) + (code - 0xdc00))
+                                }
+                                (_, code) => Some(code),
+                            };
+                            if let Some(c) = code.and_then(char::from_u32) {
+                                push_rtf_char(&mut text, group.hidden, &mut 0, c);
+                            }
+                            skip = group.unicode_skip;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            },
+            '\r' | '\n' => {}
+            c => push_rtf_char(&mut text, group.hidden, &mut skip, c),
+        }
+    }
+
+    text.trim().to_owned()
+}
+
+/// Decodes a byte in Windows-1252, the code page of `\ansi` documents and of `\'` escapes
+/// written by Word on Western systems.
+fn cp1252_char(byte: u8) -> char {
+    const C1: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+
+    // Windows-1252 agrees with Latin-1 outside of 0x80 to 0x9f, where Latin-1 has control
+    // characters and the bytes Windows-1252 leaves undefined keep them
+    match byte {
+        0x80..=0x9f => C1[(byte - 0x80) as usize],
+        byte => byte as char,
+    }
+}
+
+fn push_rtf_char(text: &mut String, hidden: bool, skip: &mut usize, c: char) {
+    if *skip > 0 {
+        *skip -= 1;
+    } else if !hidden {
+        text.push(c);
+    }
+}
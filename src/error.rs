@@ -0,0 +1,43 @@
+use std::{error::Error, fmt};
+
+use crate::models::ClipboardKind;
+
+/// Errors returned by clipboard operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClipboardError {
+    /// The backend cannot read or write the given format.
+    UnsupportedFormat(String),
+    /// Another process has the clipboard open.
+    ClipboardBusy,
+    /// The clipboard data could not be converted to or from the requested representation.
+    ConversionFailed(String),
+    /// There is no clipboard available in the current environment.
+    BackendUnavailable(String),
+    /// The requested clipboard or selection doesn't exist on this platform.
+    UnsupportedKind(ClipboardKind),
+    /// The operation did not complete in time.
+    Timeout,
+    /// The operating system reported an error.
+    Os { code: i32, message: String },
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "unsupported clipboard format: {format}"),
+            Self::ClipboardBusy => write!(f, "the clipboard is in use by another process"),
+            Self::ConversionFailed(reason) => {
+                write!(f, "failed to convert clipboard data: {reason}")
+            }
+            Self::BackendUnavailable(reason) => write!(f, "clipboard unavailable: {reason}"),
+            Self::UnsupportedKind(kind) => {
+                write!(f, "the {kind} is not supported on this platform")
+            }
+            Self::Timeout => write!(f, "timed out waiting for the clipboard"),
+            Self::Os { code, message } => write!(f, "OS error {code}: {message}"),
+        }
+    }
+}
+
+impl Error for ClipboardError {}
@@ -0,0 +1,76 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{backend::BackendFactory, error::ClipboardError};
+
+/// Controls the background clear scheduled by
+/// [`Clipboard::set_item_with_expiry`](crate::Clipboard::set_item_with_expiry).
+///
+/// Dropping the handle doesn't cancel the clear.
+#[derive(Debug)]
+pub struct ExpiryHandle {
+    cancel: Sender<()>,
+    thread: JoinHandle<Result<bool, ClipboardError>>,
+}
+
+impl ExpiryHandle {
+    /// Clears the clipboard opened by `factory` after `timeout`, unless its change count is no
+    /// longer `change_count` by then.
+    pub(crate) fn spawn(
+        factory: BackendFactory,
+        change_count: u64,
+        timeout: Duration,
+    ) -> Result<Self, ClipboardError> {
+        let (cancel, cancelled) = mpsc::channel();
+        let deadline = Instant::now() + timeout;
+
+        let thread = thread::Builder::new()
+            .name("libclipboard-expiry".to_owned())
+            .spawn(move || {
+                match cancelled.recv_timeout(timeout) {
+                    Ok(()) => return Ok(false),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // The handle was dropped, which doesn't cancel the clear
+                    Err(RecvTimeoutError::Disconnected) => {
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    }
+                }
+
+                let mut backend = factory()?;
+
+                // Someone else replaced the content in the meantime
+                if backend.change_count()? != change_count {
+                    return Ok(false);
+                }
+
+                backend.clear()?;
+                Ok(true)
+            })
+            .map_err(|error| ClipboardError::BackendUnavailable(error.to_string()))?;
+
+        Ok(Self { cancel, thread })
+    }
+
+    /// Cancels the clear if it hasn't happened yet.
+    pub fn cancel(self) {
+        let _ = self.cancel.send(());
+        let _ = self.thread.join();
+    }
+
+    /// Blocks until the timeout has elapsed, returning whether the clipboard was cleared.
+    pub fn wait(self) -> Result<bool, ClipboardError> {
+        self.thread.join().unwrap_or_else(|_| {
+            Err(ClipboardError::BackendUnavailable(
+                "the expiry thread panicked".to_owned(),
+            ))
+        })
+    }
+
+    /// Returns `true` once the clear has happened, been skipped or been cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
@@ -0,0 +1,368 @@
+use std::sync::RwLock;
+
+static CUSTOM_FORMATS: RwLock<Vec<CustomFormat>> = RwLock::new(Vec::new());
+
+/// Describes how an application-defined format is identified on each platform.
+///
+/// Formats that have not been registered use their name as every native identifier, so data
+/// copied between instances of the same application round-trips without any registration.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomFormat {
+    /// The name used in [`ClipboardItem::Custom`](crate::ClipboardItem::Custom).
+    pub name: String,
+    /// The MIME type used on Linux.
+    pub mime_type: String,
+    /// The uniform type identifier used on macOS.
+    pub uti: String,
+    /// The name passed to `RegisterClipboardFormatW` on Windows.
+    pub windows_name: String,
+}
+
+impl CustomFormat {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self {
+            mime_type: name.clone(),
+            uti: name.clone(),
+            windows_name: name.clone(),
+            name,
+        }
+    }
+
+    pub fn with_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = mime_type.into();
+        self
+    }
+
+    pub fn with_uti(mut self, uti: impl Into<String>) -> Self {
+        self.uti = uti.into();
+        self
+    }
+
+    pub fn with_windows_name(mut self, windows_name: impl Into<String>) -> Self {
+        self.windows_name = windows_name.into();
+        self
+    }
+
+    /// Adds the format to the registry, replacing any format registered under the same name.
+    pub fn register(self) {
+        let mut formats = CUSTOM_FORMATS.write().unwrap_or_else(|e| e.into_inner());
+
+        formats.retain(|format| format.name != self.name);
+        formats.push(self);
+    }
+
+    /// Returns the format registered as `name`, or an unregistered format called `name`.
+    pub fn named(name: &str) -> Self {
+        Self::find(|format| format.name == name).unwrap_or_else(|| Self::new(name))
+    }
+
+    /// Returns the format registered with the given MIME type, or an unregistered format named
+    /// after it.
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        Self::find(|format| format.mime_type == mime_type).unwrap_or_else(|| Self::new(mime_type))
+    }
+
+    /// Returns the format registered with the given UTI, or an unregistered format named after it.
+    pub fn from_uti(uti: &str) -> Self {
+        Self::find(|format| format.uti == uti).unwrap_or_else(|| Self::new(uti))
+    }
+
+    /// Returns the format registered with the given Windows format name, or an unregistered
+    /// format named after it.
+    pub fn from_windows_name(windows_name: &str) -> Self {
+        Self::find(|format| format.windows_name == windows_name)
+            .unwrap_or_else(|| Self::new(windows_name))
+    }
+
+    fn find(predicate: impl Fn(&CustomFormat) -> bool) -> Option<Self> {
+        let formats = CUSTOM_FORMATS.read().unwrap_or_else(|e| e.into_inner());
+
+        formats.iter().find(|format| predicate(format)).cloned()
+    }
+}
+
+/// The kind of data stored in a clipboard format, independent of the platform.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FormatKind {
+    Html,
+    Text,
+    UnicodeText,
+    Rtf,
+    Rtfd,
+    Url,
+    /// A list of files, read as [`ClipboardItem::FilePath`](crate::ClipboardItem::FilePath).
+    FileList,
+    Png,
+    Tiff,
+    Pdf,
+    /// A decoded image, read as [`ClipboardItem::Image`](crate::ClipboardItem::Image).
+    Image,
+    RawBytes,
+    /// An application-defined format, identified by the name of its [`CustomFormat`].
+    Custom(String),
+    /// A native format that can't be represented as a [`ClipboardItem`](crate::ClipboardItem).
+    Unknown,
+}
+
+impl FormatKind {
+    /// The order in which [`Clipboard::get_preferred`](crate::Clipboard::get_preferred) picks
+    /// formats when no preferences are given, from the richest representation to the plainest.
+    pub const FIDELITY_ORDER: &'static [FormatKind] = &[
+        FormatKind::Html,
+        FormatKind::Rtfd,
+        FormatKind::Rtf,
+        FormatKind::UnicodeText,
+        FormatKind::Text,
+        FormatKind::Url,
+        FormatKind::FileList,
+        FormatKind::Pdf,
+        FormatKind::Image,
+        FormatKind::Png,
+        FormatKind::Tiff,
+    ];
+
+    /// Returns the MIME type conventionally used for this kind of data.
+    ///
+    /// URLs and file lists share `text/uri-list`; file lists only contain `file://` URIs.
+    /// Decoded images are stored as `image/png`.
+    pub fn mime_type(&self) -> Option<String> {
+        match self {
+            Self::Custom(name) => Some(CustomFormat::named(name).mime_type),
+            kind => Some(kind.identifiers()?.mime_types.first()?.to_string()),
+        }
+    }
+
+    /// Returns the native identifiers of a built-in kind, or `None` for custom and unknown
+    /// formats.
+    pub fn identifiers(&self) -> Option<&'static FormatIdentifiers> {
+        FORMAT_REGISTRY
+            .iter()
+            .find(|identifiers| identifiers.kind == *self)
+    }
+
+    /// Returns the built-in kind of a MIME type, ignoring case and whitespace.
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        let mime_type: String = mime_type.split_whitespace().collect();
+
+        Self::find(|identifiers| {
+            identifiers
+                .mime_types
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(&mime_type))
+        })
+    }
+
+    /// Returns the built-in kind of a uniform type identifier.
+    pub fn from_uti(uti: &str) -> Option<Self> {
+        Self::find(|identifiers| identifiers.utis.contains(&uti))
+    }
+
+    /// Returns the built-in kind of a Windows clipboard format, given its id and, for registered
+    /// formats, its name.
+    pub fn from_windows_format(id: u32, name: Option<&str>) -> Option<Self> {
+        Self::find(|identifiers| {
+            identifiers
+                .windows_formats
+                .iter()
+                .any(|format| match (format, name) {
+                    (WindowsFormat::Standard { id: known, .. }, _) => *known == id,
+                    (WindowsFormat::Registered(known), Some(name)) => *known == name,
+                    (WindowsFormat::Registered(_), None) => false,
+                })
+        })
+    }
+
+    /// Returns the built-in kind of an X11 selection target.
+    pub fn from_x11_target(target: &str) -> Option<Self> {
+        Self::find(|identifiers| identifiers.x11_targets.contains(&target))
+    }
+
+    /// Kinds that share an identifier resolve to the first one in the registry.
+    fn find(predicate: impl Fn(&FormatIdentifiers) -> bool) -> Option<Self> {
+        FORMAT_REGISTRY
+            .iter()
+            .find(|identifiers| predicate(identifiers))
+            .map(|identifiers| identifiers.kind.clone())
+    }
+}
+
+/// How the data of a [`FormatKind`] is identified on every platform.
+///
+/// Each list starts with the identifier that is written; the others are aliases that are
+/// recognized when reading. A list is empty if the platform has no equivalent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FormatIdentifiers {
+    pub kind: FormatKind,
+    pub mime_types: &'static [&'static str],
+    /// Uniform type identifiers, as used by macOS pasteboards.
+    pub utis: &'static [&'static str],
+    pub windows_formats: &'static [WindowsFormat],
+    /// Selection targets, as used by X11.
+    pub x11_targets: &'static [&'static str],
+}
+
+impl FormatIdentifiers {
+    /// Returns the identifiers of every built-in kind.
+    pub fn all() -> &'static [FormatIdentifiers] {
+        FORMAT_REGISTRY
+    }
+}
+
+/// A Windows clipboard format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowsFormat {
+    /// A predefined format with a fixed id, such as `CF_UNICODETEXT`.
+    Standard { id: u32, name: &'static str },
+    /// A format whose id is obtained by passing its name to `RegisterClipboardFormatW`.
+    Registered(&'static str),
+}
+
+impl WindowsFormat {
+    pub const TEXT: Self = Self::Standard {
+        id: 1,
+        name: "CF_TEXT",
+    };
+    pub const TIFF: Self = Self::Standard {
+        id: 6,
+        name: "CF_TIFF",
+    };
+    pub const DIB: Self = Self::Standard {
+        id: 8,
+        name: "CF_DIB",
+    };
+    pub const UNICODETEXT: Self = Self::Standard {
+        id: 13,
+        name: "CF_UNICODETEXT",
+    };
+    pub const HDROP: Self = Self::Standard {
+        id: 15,
+        name: "CF_HDROP",
+    };
+}
+
+/// The order matters where kinds share an identifier: `text/uri-list` is read as a URL and
+/// `image/png` as a PNG file rather than decoded pixels.
+static FORMAT_REGISTRY: &[FormatIdentifiers] = &[
+    FormatIdentifiers {
+        kind: FormatKind::Html,
+        mime_types: &["text/html"],
+        utis: &["public.html"],
+        windows_formats: &[WindowsFormat::Registered("HTML Format")],
+        x11_targets: &["text/html"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Text,
+        mime_types: &["text/plain"],
+        utis: &["public.plain-text"],
+        windows_formats: &[WindowsFormat::TEXT],
+        x11_targets: &["text/plain", "STRING", "TEXT"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::UnicodeText,
+        mime_types: &["text/plain;charset=utf-8"],
+        utis: &["public.utf8-plain-text"],
+        windows_formats: &[WindowsFormat::UNICODETEXT],
+        x11_targets: &["UTF8_STRING", "text/plain;charset=utf-8"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Rtf,
+        mime_types: &["text/rtf", "application/rtf"],
+        utis: &["public.rtf"],
+        windows_formats: &[WindowsFormat::Registered("Rich Text Format")],
+        x11_targets: &["text/rtf", "application/rtf"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Rtfd,
+        mime_types: &["application/x-rtfd"],
+        utis: &["com.apple.flat-rtfd"],
+        windows_formats: &[],
+        x11_targets: &["application/x-rtfd"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Url,
+        mime_types: &["text/uri-list", "text/x-moz-url"],
+        utis: &["public.url"],
+        windows_formats: &[WindowsFormat::Registered("UniformResourceLocatorW")],
+        x11_targets: &["text/uri-list", "text/x-moz-url"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::FileList,
+        mime_types: &["text/uri-list"],
+        utis: &["public.file-url"],
+        windows_formats: &[WindowsFormat::HDROP],
+        x11_targets: &["text/uri-list", "x-special/gnome-copied-files"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Png,
+        mime_types: &["image/png"],
+        utis: &["public.png"],
+        windows_formats: &[WindowsFormat::Registered("PNG")],
+        x11_targets: &["image/png"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Tiff,
+        mime_types: &["image/tiff"],
+        utis: &["public.tiff"],
+        windows_formats: &[WindowsFormat::TIFF],
+        x11_targets: &["image/tiff"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Pdf,
+        mime_types: &["application/pdf"],
+        utis: &["com.adobe.pdf"],
+        windows_formats: &[WindowsFormat::Registered("Portable Document Format")],
+        x11_targets: &["application/pdf"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::Image,
+        mime_types: &["image/png"],
+        utis: &["public.png"],
+        windows_formats: &[WindowsFormat::DIB],
+        x11_targets: &["image/png"],
+    },
+    FormatIdentifiers {
+        kind: FormatKind::RawBytes,
+        mime_types: &["application/octet-stream"],
+        utis: &["public.data"],
+        windows_formats: &[],
+        x11_targets: &["application/octet-stream"],
+    },
+];
+
+/// The identifier a platform uses for a clipboard format.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum NativeFormat {
+    /// A uniform type identifier, as used by macOS pasteboards.
+    Uti(String),
+    /// A MIME type.
+    MimeType(String),
+    /// A Windows clipboard format, with its name if it is a registered format.
+    Windows { id: u32, name: Option<String> },
+    /// The name of an X11 selection target, which is a MIME type or one of the older names
+    /// such as `UTF8_STRING`.
+    X11Target(String),
+    /// An X11 selection target with the type and format of the property its owner answered
+    /// with, which snapshots keep to put back exactly what the owner offered.
+    X11Property {
+        target: String,
+        property_type: String,
+        format: u8,
+    },
+}
+
+/// Describes a format that is currently available on the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatInfo {
+    pub kind: FormatKind,
+    pub native: NativeFormat,
+    /// The size of the data in bytes, if the backend can tell without fetching it.
+    pub size: Option<usize>,
+}
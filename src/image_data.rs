@@ -0,0 +1,205 @@
+use std::{fmt::Display, io::Cursor};
+
+use image::{
+    codecs::{
+        bmp::{BmpDecoder, BmpEncoder},
+        jpeg::{JpegEncoder, PixelDensity},
+        tiff::TiffEncoder,
+    },
+    ColorType, DynamicImage, ImageEncoder,
+};
+
+use crate::error::ClipboardError;
+
+/// Size of the `BITMAPFILEHEADER` that a BMP file has in front of its DIB.
+const BMP_FILE_HEADER_SIZE: usize = 14;
+/// Offset of `biXPelsPerMeter` and `biYPelsPerMeter` in a DIB header.
+const DIB_RESOLUTION_OFFSET: usize = 24;
+const JPEG_QUALITY: u8 = 90;
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// An encoded image format that [`ImageData`] can be decoded from and encoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ImageFormat {
+    Png,
+    Tiff,
+    /// A BMP file, including its file header.
+    Bmp,
+    /// A device-independent bitmap, as stored in the Windows `CF_DIB` format.
+    Dib,
+    /// A JPEG file. JPEG has no alpha channel, so transparency is lost when encoding.
+    Jpeg,
+}
+
+/// An uncompressed image, read as [`ClipboardItem::Image`](crate::ClipboardItem::Image).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8 pixels, row by row from the top-left corner.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::bytes"))]
+    pub rgba: Vec<u8>,
+    /// The resolution in dots per inch, if the image carries one.
+    pub dpi: Option<u32>,
+}
+
+impl ImageData {
+    /// Returns an image without a resolution, checking that `rgba` holds `width * height`
+    /// pixels.
+    pub fn new(width: u32, height: u32, rgba: Vec<u8>) -> Result<Self, ClipboardError> {
+        check_size(width, height, rgba.len())?;
+
+        Ok(Self {
+            width,
+            height,
+            rgba,
+            dpi: None,
+        })
+    }
+
+    pub fn decode(data: &[u8], format: ImageFormat) -> Result<Self, ClipboardError> {
+        let (image, dpi) = match format {
+            ImageFormat::Png => (
+                image::load_from_memory_with_format(data, image::ImageFormat::Png),
+                png_dpi(data),
+            ),
+            ImageFormat::Tiff => (
+                image::load_from_memory_with_format(data, image::ImageFormat::Tiff),
+                None,
+            ),
+            ImageFormat::Bmp => (
+                image::load_from_memory_with_format(data, image::ImageFormat::Bmp),
+                data.get(BMP_FILE_HEADER_SIZE..).and_then(dib_dpi),
+            ),
+            ImageFormat::Dib => (
+                BmpDecoder::new_without_file_header(Cursor::new(data))
+                    .and_then(DynamicImage::from_decoder),
+                dib_dpi(data),
+            ),
+            ImageFormat::Jpeg => (
+                image::load_from_memory_with_format(data, image::ImageFormat::Jpeg),
+                None,
+            ),
+        };
+
+        let image = image.map_err(conversion_failed)?.into_rgba8();
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            rgba: image.into_raw(),
+            dpi,
+        })
+    }
+
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, ClipboardError> {
+        // The fields are public, and the encoders panic on a buffer of the wrong size
+        check_size(self.width, self.height, self.rgba.len())?;
+
+        let mut data = Vec::new();
+
+        match format {
+            ImageFormat::Png => self.encode_png(&mut data)?,
+            ImageFormat::Tiff => TiffEncoder::new(Cursor::new(&mut data))
+                .write_image(&self.rgba, self.width, self.height, ColorType::Rgba8)
+                .map_err(conversion_failed)?,
+            ImageFormat::Bmp => self.encode_bmp(&mut data)?,
+            ImageFormat::Dib => {
+                self.encode_bmp(&mut data)?;
+                data.drain(..BMP_FILE_HEADER_SIZE);
+            }
+            ImageFormat::Jpeg => {
+                let mut encoder = JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY);
+
+                if let Some(dpi) = self.dpi {
+                    encoder.set_pixel_density(PixelDensity::dpi(dpi.min(u16::MAX as u32) as u16));
+                }
+
+                encoder
+                    .write_image(&self.rgba, self.width, self.height, ColorType::Rgba8)
+                    .map_err(conversion_failed)?;
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Writes the PNG with the `png` crate directly, as `image` can't store the resolution.
+    fn encode_png(&self, data: &mut Vec<u8>) -> Result<(), ClipboardError> {
+        let mut encoder = png::Encoder::new(data, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        if let Some(dpi) = self.dpi {
+            let ppm = dpi_to_ppm(dpi);
+
+            encoder.set_pixel_dims(Some(png::PixelDimensions {
+                xppu: ppm,
+                yppu: ppm,
+                unit: png::Unit::Meter,
+            }));
+        }
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.rgba))
+            .map_err(conversion_failed)
+    }
+
+    fn encode_bmp(&self, data: &mut Vec<u8>) -> Result<(), ClipboardError> {
+        BmpEncoder::new(data)
+            .write_image(&self.rgba, self.width, self.height, ColorType::Rgba8)
+            .map_err(conversion_failed)?;
+
+        if let Some(dpi) = self.dpi {
+            let ppm = (dpi_to_ppm(dpi) as i32).to_le_bytes();
+            let offset = BMP_FILE_HEADER_SIZE + DIB_RESOLUTION_OFFSET;
+
+            data[offset..offset + 4].copy_from_slice(&ppm);
+            data[offset + 4..offset + 8].copy_from_slice(&ppm);
+        }
+
+        Ok(())
+    }
+}
+
+fn check_size(width: u32, height: u32, len: usize) -> Result<(), ClipboardError> {
+    let expected = width as usize * height as usize * 4;
+
+    if len != expected {
+        return Err(ClipboardError::ConversionFailed(format!(
+            "expected {expected} bytes of RGBA8 pixels for a {width}x{height} image, got {len}"
+        )));
+    }
+
+    Ok(())
+}
+
+fn png_dpi(data: &[u8]) -> Option<u32> {
+    let reader = png::Decoder::new(data).read_info().ok()?;
+    let dims = reader.info().pixel_dims?;
+
+    (dims.unit == png::Unit::Meter).then(|| ppm_to_dpi(dims.xppu))
+}
+
+fn dib_dpi(dib: &[u8]) -> Option<u32> {
+    let ppm = dib.get(DIB_RESOLUTION_OFFSET..DIB_RESOLUTION_OFFSET + 4)?;
+    let ppm = i32::from_le_bytes(ppm.try_into().ok()?);
+
+    (ppm > 0).then(|| ppm_to_dpi(ppm as u32))
+}
+
+fn dpi_to_ppm(dpi: u32) -> u32 {
+    (dpi as f64 / METERS_PER_INCH).round() as u32
+}
+
+fn ppm_to_dpi(ppm: u32) -> u32 {
+    (ppm as f64 * METERS_PER_INCH).round() as u32
+}
+
+fn conversion_failed(error: impl Display) -> ClipboardError {
+    ClipboardError::ConversionFailed(error.to_string())
+}
@@ -1,7 +1,39 @@
 #[cfg(target_os = "macos")]
 pub mod macos;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod wayland;
 #[cfg(target_os = "windows")]
 pub mod windows;
+#[cfg(all(unix, not(target_os = "macos")))]
+pub mod x11;
 
+pub mod memory;
+
+#[cfg(feature = "async")]
+mod async_clipboard;
+#[cfg(feature = "async")]
+pub use async_clipboard::*;
+
+mod backend;
+mod convert;
+mod error;
+mod expiry;
+mod formats;
+mod image_data;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod mime;
 mod models;
+mod options;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod snapshot;
+mod watcher;
+pub use backend::*;
+pub use error::*;
+pub use expiry::*;
+pub use formats::*;
+pub use image_data::*;
 pub use models::*;
+pub use options::*;
+pub use snapshot::*;
+pub use watcher::*;
@@ -1,20 +1,32 @@
-use std::{io::Cursor, sync::Once};
+use std::{cell::Cell, io::Cursor, ops::Deref, sync::Arc};
 
 use icrate::{
     objc2::rc::Id,
     AppKit::{
-        NSPasteboard, NSPasteboardTypeColor, NSPasteboardTypeFileURL, NSPasteboardTypeFont,
-        NSPasteboardTypeHTML, NSPasteboardTypePDF, NSPasteboardTypePNG, NSPasteboardTypeRTF,
-        NSPasteboardTypeRTFD, NSPasteboardTypeRuler, NSPasteboardTypeSound, NSPasteboardTypeString,
-        NSPasteboardTypeTIFF, NSPasteboardTypeTabularText, NSPasteboardTypeURL,
+        NSPasteboard, NSPasteboardNameFind, NSPasteboardTypeColor, NSPasteboardTypeFileURL,
+        NSPasteboardTypeFont, NSPasteboardTypeHTML, NSPasteboardTypePDF, NSPasteboardTypePNG,
+        NSPasteboardTypeRTF, NSPasteboardTypeRTFD, NSPasteboardTypeRuler, NSPasteboardTypeSound,
+        NSPasteboardTypeString, NSPasteboardTypeTIFF, NSPasteboardTypeTabularText,
+        NSPasteboardTypeURL,
     },
-    Foundation::{NSMutableArray, NSString},
+    Foundation::{NSData, NSMutableArray, NSString, NSURL},
 };
 
-use crate::models::ClipboardItem;
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    error::ClipboardError,
+    formats::{CustomFormat, FormatInfo, FormatKind, NativeFormat},
+    image_data::ImageFormat,
+    models::{ClipboardItem, ClipboardKind},
+    options::SetOptions,
+    snapshot::{ClipboardSnapshot, SnapshotEntry},
+};
 
-static INIT: Once = Once::new();
-static mut CHANGE_COUNT: isize = 0;
+/// Types that tell clipboard managers how to treat the other types on the pasteboard.
+const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+const TRANSIENT_TYPE: &str = "org.nspasteboard.TransientType";
+/// Text in an unspecified encoding, which AppKit has no constant for.
+const PLAIN_TEXT_TYPE: &str = "public.plain-text";
 
 #[derive(Debug)]
 enum PasteType {
@@ -25,6 +37,7 @@ enum PasteType {
     Html,
     MultipleTextSelection,
     Pdf,
+    PlainText,
     Png,
     Rtf,
     Rtfd,
@@ -35,9 +48,41 @@ enum PasteType {
     Tiff,
 }
 
+/// A pasteboard type that is either provided by AppKit or created for a custom format.
+enum PasteboardType {
+    Static(&'static NSString),
+    Custom(Id<NSString>),
+}
+
+impl Deref for PasteboardType {
+    type Target = NSString;
+
+    fn deref(&self) -> &NSString {
+        match self {
+            Self::Static(pastetype) => pastetype,
+            Self::Custom(pastetype) => pastetype,
+        }
+    }
+}
+
+/// How an item is written to the pasteboard.
+enum Payload<'a> {
+    String(&'a str),
+    /// A string converted from the item while writing it.
+    Converted(String),
+    Data(&'a [u8]),
+    /// Data encoded from the item while writing it.
+    Encoded(Vec<u8>),
+}
+
 #[derive(Debug, Clone)]
 pub struct MacOSCC {
     pasteboard: Id<NSPasteboard>,
+    kind: ClipboardKind,
+    /// The change count of the pasteboard right after this instance last wrote to it.
+    owned_change_count: Option<isize>,
+    /// The change count of the pasteboard when `has_clipboard_changed` was last called.
+    last_change_count: Cell<isize>,
 }
 
 impl PartialEq for MacOSCC {
@@ -48,42 +93,169 @@ impl PartialEq for MacOSCC {
 
 impl MacOSCC {
     pub fn new() -> Self {
+        Self::with_pasteboard(
+            unsafe { NSPasteboard::generalPasteboard() },
+            ClipboardKind::Clipboard,
+        )
+    }
+
+    /// Opens the pasteboard for `kind`. The X11 selections don't exist on macOS.
+    pub fn with_kind(kind: ClipboardKind) -> Result<Self, ClipboardError> {
+        let pasteboard = match &kind {
+            ClipboardKind::Clipboard => unsafe { NSPasteboard::generalPasteboard() },
+            ClipboardKind::Find => unsafe {
+                NSPasteboard::pasteboardWithName(NSPasteboardNameFind)
+            },
+            ClipboardKind::Named(name) => unsafe {
+                NSPasteboard::pasteboardWithName(&NSString::from_str(name))
+            },
+            ClipboardKind::Primary | ClipboardKind::Secondary => {
+                return Err(ClipboardError::UnsupportedKind(kind))
+            }
+        };
+
+        Ok(Self::with_pasteboard(pasteboard, kind))
+    }
+
+    fn with_pasteboard(pasteboard: Id<NSPasteboard>, kind: ClipboardKind) -> Self {
         Self {
-            pasteboard: unsafe { NSPasteboard::generalPasteboard() },
+            last_change_count: Cell::new(unsafe { pasteboard.changeCount() }),
+            pasteboard,
+            kind,
+            owned_change_count: None,
         }
     }
 
-    pub fn get_clipboard_item(&self) -> Option<ClipboardItem> {
-        let types = unsafe { self.pasteboard.types()? };
-        let general_type = unsafe { self.pasteboard.availableTypeFromArray(&types)? };
+    pub fn get_clipboard_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let types = match unsafe { self.pasteboard.types() } {
+            Some(types) => types,
+            None => return Ok(None),
+        };
 
-        let pastetype = Self::get_paste_type(general_type.as_ref())?;
+        Ok(types.into_iter().find_map(|t| self.get_item_for_type(&t)))
+    }
 
-        self.paste_type_as_clipboard_item(pastetype)
+    pub fn get_clipboard_format(
+        &self,
+        kind: &FormatKind,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let types = match unsafe { self.pasteboard.types() } {
+            Some(types) => types,
+            None => return Ok(None),
+        };
+
+        Ok(types
+            .into_iter()
+            .filter(|t| Self::get_type_kind(t) == *kind)
+            .find_map(|t| self.get_item_for_type(&t)))
     }
 
-    pub fn set_clipboard_item(&mut self, item: ClipboardItem) {
-        match item {
-            ClipboardItem::Text(string) => self.set_string_from_clipboard(string),
-            ClipboardItem::UnicodeText(string) => self.set_string_from_clipboard(string),
-            _ => todo!(),
+    pub fn set_clipboard_item(&mut self, item: ClipboardItem) -> Result<(), ClipboardError> {
+        self.set_clipboard_items(&[item])
+    }
+
+    pub fn set_clipboard_items(&mut self, items: &[ClipboardItem]) -> Result<(), ClipboardError> {
+        self.set_clipboard_items_with_options(items, &SetOptions::default())
+    }
+
+    pub fn set_clipboard_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        let mut payloads: Vec<(PasteboardType, Payload<'_>)> = Vec::with_capacity(items.len());
+
+        for item in items {
+            let (pastetype, payload) = Self::get_payload(item)?;
+
+            // The first representation of a type wins, matching the other backends
+            if !payloads
+                .iter()
+                .any(|(existing, _)| **existing == *pastetype)
+            {
+                payloads.push((pastetype, payload));
+            }
+        }
+
+        // Markers from nspasteboard.org; clipboard managers only check whether they are present
+        if options.concealed {
+            payloads.push((
+                PasteboardType::Custom(NSString::from_str(CONCEALED_TYPE)),
+                Payload::Data(&[]),
+            ));
+        }
+
+        if options.transient || options.exclude_from_history {
+            payloads.push((
+                PasteboardType::Custom(NSString::from_str(TRANSIENT_TYPE)),
+                Payload::Data(&[]),
+            ));
+        }
+
+        unsafe {
+            let mut array = NSMutableArray::array();
+            for (pastetype, _) in &payloads {
+                array.addObject(&**pastetype);
+            }
+
+            // Takes ownership of the pasteboard once for all of the representations
+            self.pasteboard.declareTypes_owner(&array, None);
+
+            for (pastetype, payload) in payloads {
+                let written = match payload {
+                    Payload::String(string) => self
+                        .pasteboard
+                        .setString_forType(&NSString::from_str(string), &pastetype),
+                    Payload::Converted(string) => self
+                        .pasteboard
+                        .setString_forType(&NSString::from_str(&string), &pastetype),
+                    Payload::Data(data) => self
+                        .pasteboard
+                        .setData_forType(Some(&NSData::with_bytes(data)), &pastetype),
+                    Payload::Encoded(data) => self
+                        .pasteboard
+                        .setData_forType(Some(&NSData::with_bytes(&data)), &pastetype),
+                };
+
+                // Writing only fails if another process took ownership of the pasteboard in between
+                if !written {
+                    return Err(ClipboardError::ClipboardBusy);
+                }
+            }
+
+            self.owned_change_count = Some(self.pasteboard.changeCount());
         }
+
+        Ok(())
     }
 
-    pub fn get_clipboard_items(&self) -> Option<Vec<ClipboardItem>> {
-        let types = unsafe { self.pasteboard.types()? };
+    pub fn get_clipboard_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        let types = match unsafe { self.pasteboard.types() } {
+            Some(types) => types,
+            None => return Ok(Vec::new()),
+        };
 
         let mut result = Vec::new();
 
         for t in types.into_iter() {
-            let pastetype = match Self::get_paste_type(&t) {
-                Some(t) => t,
-                None => continue,
-            };
-            result.push(self.paste_type_as_clipboard_item(pastetype)?);
+            if let Some(item) = self.get_item_for_type(&t) {
+                result.push(item);
+            }
         }
 
-        Some(result)
+        Ok(result)
+    }
+
+    /// Empties the pasteboard, returning `true` if its contents were written by this instance.
+    pub fn clear_clipboard(&mut self) -> Result<bool, ClipboardError> {
+        let owned = unsafe {
+            let owned = self.owned_change_count == Some(self.pasteboard.changeCount());
+            self.pasteboard.clearContents();
+            owned
+        };
+
+        self.owned_change_count = None;
+        Ok(owned)
     }
 
     pub fn get_number_of_formats(&self) -> i32 {
@@ -93,18 +265,119 @@ impl MacOSCC {
         0
     }
 
-    pub fn has_clipboard_changed(&self) -> bool {
+    pub fn get_available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        let types = match unsafe { self.pasteboard.types() } {
+            Some(types) => types,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(types
+            .into_iter()
+            .map(|t| FormatInfo {
+                kind: Self::get_type_kind(&t),
+                native: NativeFormat::Uti(t.to_string()),
+                // Getting the size would make the owner provide promised data
+                size: None,
+            })
+            .collect())
+    }
+
+    /// Captures the data of every type on the pasteboard.
+    ///
+    /// Only the first pasteboard item is captured, matching what the other readers see.
+    pub fn snapshot_clipboard(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        let types = match unsafe { self.pasteboard.types() } {
+            Some(types) => types,
+            None => return Ok(ClipboardSnapshot::default()),
+        };
+
+        Ok(ClipboardSnapshot {
+            entries: types
+                .into_iter()
+                .filter_map(|t| {
+                    Some(SnapshotEntry {
+                        data: unsafe { self.pasteboard.dataForType(&t)? }.bytes().to_vec(),
+                        native: NativeFormat::Uti(t.to_string()),
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    pub fn restore_clipboard(
+        &mut self,
+        snapshot: &ClipboardSnapshot,
+    ) -> Result<(), ClipboardError> {
+        let types = snapshot
+            .entries
+            .iter()
+            .map(|entry| match &entry.native {
+                NativeFormat::Uti(uti) => Ok((NSString::from_str(uti), entry.data.as_slice())),
+                native => Err(ClipboardError::UnsupportedFormat(format!("{native:?}"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         unsafe {
-            INIT.call_once(|| {
-                CHANGE_COUNT = self.pasteboard.changeCount();
-            });
-
-            if CHANGE_COUNT != self.pasteboard.changeCount() {
-                CHANGE_COUNT = self.pasteboard.changeCount();
-                true
-            } else {
-                false
+            let mut array = NSMutableArray::array();
+            for (pastetype, _) in &types {
+                array.addObject(&**pastetype);
             }
+
+            self.pasteboard.declareTypes_owner(&array, None);
+
+            for (pastetype, data) in types {
+                if !self
+                    .pasteboard
+                    .setData_forType(Some(&NSData::with_bytes(data)), &pastetype)
+                {
+                    return Err(ClipboardError::ClipboardBusy);
+                }
+            }
+
+            self.owned_change_count = Some(self.pasteboard.changeCount());
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the pasteboard's change count changed since the last call.
+    pub fn has_clipboard_changed(&self) -> bool {
+        let change_count = unsafe { self.pasteboard.changeCount() };
+        self.last_change_count.replace(change_count) != change_count
+    }
+
+    /// Reads the markers written by `set_clipboard_items_with_options`.
+    pub fn get_clipboard_options(&self) -> Result<SetOptions, ClipboardError> {
+        let mut options = SetOptions::default();
+
+        for t in unsafe { self.pasteboard.types() }.into_iter().flatten() {
+            match t.to_string().as_str() {
+                CONCEALED_TYPE => options.concealed = true,
+                TRANSIENT_TYPE => {
+                    options.transient = true;
+                    options.exclude_from_history = true;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn get_item_for_type(&self, pastetype: &NSString) -> Option<ClipboardItem> {
+        if Self::is_marker_type(pastetype) {
+            return None;
+        }
+
+        match Self::get_paste_type(pastetype) {
+            Some(pastetype) => self.paste_type_as_clipboard_item(pastetype),
+            // Types this crate doesn't know about are returned as raw data
+            None => Some(ClipboardItem::Custom {
+                format: CustomFormat::from_uti(&pastetype.to_string()).name,
+                data: unsafe { self.pasteboard.dataForType(pastetype)? }
+                    .bytes()
+                    .to_vec(),
+            }),
         }
     }
 
@@ -129,29 +402,126 @@ impl MacOSCC {
             PasteType::Png => ClipboardItem::Png(self.get_png_from_clipboard()?),
             PasteType::Tiff => ClipboardItem::Tiff(self.get_tiff_from_clipboard()?),
             PasteType::Pdf => ClipboardItem::Pdf(self.get_pdf_from_clipboard()?),
+            PasteType::PlainText => ClipboardItem::Text(self.get_plain_text_from_clipboard()?),
         })
     }
 
+    fn get_type_kind(pastetype: &NSString) -> FormatKind {
+        if Self::is_marker_type(pastetype) {
+            return FormatKind::Unknown;
+        }
+
+        match Self::get_paste_type(pastetype) {
+            Some(pastetype) => Self::paste_type_kind(&pastetype),
+            None => FormatKind::Custom(CustomFormat::from_uti(&pastetype.to_string()).name),
+        }
+    }
+
+    /// Markers describe the other types rather than holding any content.
+    fn is_marker_type(pastetype: &NSString) -> bool {
+        matches!(
+            pastetype.to_string().as_str(),
+            CONCEALED_TYPE | TRANSIENT_TYPE
+        )
+    }
+
+    /// Returns the kind of item `paste_type_as_clipboard_item` creates for a paste type.
+    fn paste_type_kind(pastetype: &PasteType) -> FormatKind {
+        match pastetype {
+            PasteType::Url => FormatKind::Url,
+            PasteType::FileURL => FormatKind::FileList,
+            PasteType::Html => FormatKind::Html,
+            PasteType::MultipleTextSelection | PasteType::PlainText => FormatKind::Text,
+            PasteType::Rtf => FormatKind::Rtf,
+            PasteType::Rtfd => FormatKind::Rtfd,
+            PasteType::Png => FormatKind::Png,
+            PasteType::Tiff => FormatKind::Tiff,
+            PasteType::Pdf => FormatKind::Pdf,
+            PasteType::Color
+            | PasteType::Font
+            | PasteType::Ruler
+            | PasteType::Sound
+            | PasteType::String
+            | PasteType::TabularText => FormatKind::UnicodeText,
+        }
+    }
+
     fn get_paste_type(string: &NSString) -> Option<PasteType> {
-        match string.to_string().as_str() {
-            "public.url" => Some(PasteType::Url),
-            "com.apple.cocoa.pasteboard.color" => Some(PasteType::Color),
-            "public.file-url" => Some(PasteType::FileURL),
-            "com.apple.cocoa.pasteboard.character-formatting" => Some(PasteType::Font),
-            "public.html" => Some(PasteType::Html),
-            "com.apple.cocoa.pasteboard.multiple-text-selection" => {
-                Some(PasteType::MultipleTextSelection)
-            }
-            "com.adobe.pdf" => Some(PasteType::Pdf),
-            "public.png" => Some(PasteType::Png),
-            "public.rtf" => Some(PasteType::Rtf),
-            "com.apple.flat-rtfd" => Some(PasteType::Rtfd),
-            "com.apple.cocoa.pasteboard.paragraph-formatting" => Some(PasteType::Ruler),
-            "com.apple.cocoa.pasteboard.sound" => Some(PasteType::Sound),
-            "public.utf8-plain-text" => Some(PasteType::String),
-            "public.utf8-tab-separated-values-text" => Some(PasteType::TabularText),
-            "public.tiff" => Some(PasteType::Tiff),
-            _ => None,
+        let uti = string.to_string();
+
+        Some(match FormatKind::from_uti(&uti) {
+            Some(FormatKind::Url) => PasteType::Url,
+            Some(FormatKind::FileList) => PasteType::FileURL,
+            Some(FormatKind::Html) => PasteType::Html,
+            Some(FormatKind::Pdf) => PasteType::Pdf,
+            Some(FormatKind::Png) => PasteType::Png,
+            Some(FormatKind::Rtf) => PasteType::Rtf,
+            Some(FormatKind::Rtfd) => PasteType::Rtfd,
+            Some(FormatKind::Text) => PasteType::PlainText,
+            Some(FormatKind::UnicodeText) => PasteType::String,
+            Some(FormatKind::Tiff) => PasteType::Tiff,
+            // AppKit types that are read as text but have no kind of their own
+            _ => match uti.as_str() {
+                "com.apple.cocoa.pasteboard.color" => PasteType::Color,
+                "com.apple.cocoa.pasteboard.character-formatting" => PasteType::Font,
+                "com.apple.cocoa.pasteboard.multiple-text-selection" => {
+                    PasteType::MultipleTextSelection
+                }
+                "com.apple.cocoa.pasteboard.paragraph-formatting" => PasteType::Ruler,
+                "com.apple.cocoa.pasteboard.sound" => PasteType::Sound,
+                "public.utf8-tab-separated-values-text" => PasteType::TabularText,
+                _ => return None,
+            },
+        })
+    }
+
+    fn get_payload(item: &ClipboardItem) -> Result<(PasteboardType, Payload<'_>), ClipboardError> {
+        use PasteboardType::Static;
+
+        unsafe {
+            Ok(match item {
+                ClipboardItem::Text(string) | ClipboardItem::UnicodeText(string) => {
+                    (Static(NSPasteboardTypeString), Payload::String(string))
+                }
+                ClipboardItem::Html(string) => {
+                    (Static(NSPasteboardTypeHTML), Payload::String(string))
+                }
+                ClipboardItem::Rtf(string) => {
+                    (Static(NSPasteboardTypeRTF), Payload::String(string))
+                }
+                ClipboardItem::Rtfd(string) => {
+                    (Static(NSPasteboardTypeRTFD), Payload::String(string))
+                }
+                ClipboardItem::Url(string) => {
+                    (Static(NSPasteboardTypeURL), Payload::String(string))
+                }
+                ClipboardItem::FilePath(path) => (
+                    Static(NSPasteboardTypeFileURL),
+                    Payload::Converted(file_url(path)),
+                ),
+                ClipboardItem::Png(data) => {
+                    (Static(NSPasteboardTypePNG), Payload::Data(data.get_ref()))
+                }
+                ClipboardItem::Tiff(data) => {
+                    (Static(NSPasteboardTypeTIFF), Payload::Data(data.get_ref()))
+                }
+                ClipboardItem::Pdf(data) => {
+                    (Static(NSPasteboardTypePDF), Payload::Data(data.get_ref()))
+                }
+                ClipboardItem::Image(image) => (
+                    Static(NSPasteboardTypePNG),
+                    Payload::Encoded(image.encode(ImageFormat::Png)?),
+                ),
+                ClipboardItem::Custom { format, data } => (
+                    PasteboardType::Custom(NSString::from_str(&CustomFormat::named(format).uti)),
+                    Payload::Data(data),
+                ),
+                item => {
+                    return Err(ClipboardError::UnsupportedFormat(
+                        item.format_name().to_owned(),
+                    ))
+                }
+            })
         }
     }
 
@@ -167,11 +537,12 @@ impl MacOSCC {
 
     fn get_file_url_from_clipboard(&self) -> Option<String> {
         unsafe {
-            Some(
-                self.pasteboard
+            Some(file_path(
+                &self
+                    .pasteboard
                     .stringForType(NSPasteboardTypeFileURL)?
                     .to_string(),
-            )
+            ))
         }
     }
 
@@ -185,15 +556,13 @@ impl MacOSCC {
         }
     }
 
-    fn set_string_from_clipboard(&mut self, string: String) {
+    fn get_plain_text_from_clipboard(&self) -> Option<String> {
         unsafe {
-            let mut array = NSMutableArray::array();
-            array.addObject(NSPasteboardTypeString);
-
-            self.pasteboard.declareTypes_owner(&array, None);
-
-            self.pasteboard
-                .setString_forType(&NSString::from_str(&string), NSPasteboardTypeString);
+            Some(
+                self.pasteboard
+                    .stringForType(&NSString::from_str(PLAIN_TEXT_TYPE))?
+                    .to_string(),
+            )
         }
     }
 
@@ -321,8 +690,89 @@ impl MacOSCC {
     }
 }
 
+impl ClipboardBackend for MacOSCC {
+    fn kind(&self) -> ClipboardKind {
+        self.kind.clone()
+    }
+
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_item()
+    }
+
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_items()
+    }
+
+    fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_format(kind)
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.set_clipboard_items_with_options(items, options)
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        self.get_clipboard_options()
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.clear_clipboard()
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        self.get_available_formats()
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        self.snapshot_clipboard()
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.restore_clipboard(snapshot)
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        Ok(unsafe { self.pasteboard.changeCount() } as u64)
+    }
+
+    fn has_changed(&self) -> bool {
+        self.has_clipboard_changed()
+    }
+
+    fn factory(&self) -> BackendFactory {
+        let kind = self.kind.clone();
+
+        Arc::new(move || Ok(Box::new(MacOSCC::with_kind(kind.clone())?)))
+    }
+}
+
 impl Default for MacOSCC {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Returns the percent-encoded `file://` URL of `path`, as Finder writes it.
+fn file_url(path: &str) -> String {
+    unsafe {
+        NSURL::fileURLWithPath(&NSString::from_str(path))
+            .absoluteString()
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the path of a `file://` URL, keeping strings that aren't one as they are.
+fn file_path(url: &str) -> String {
+    unsafe {
+        NSURL::URLWithString(&NSString::from_str(url))
+            .filter(|url| url.isFileURL())
+            .and_then(|url| url.path())
+            .map(|path| path.to_string())
+            .unwrap_or_else(|| url.to_owned())
+    }
+}
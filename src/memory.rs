@@ -0,0 +1,356 @@
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    error::ClipboardError,
+    formats::{CustomFormat, FormatInfo, FormatKind, NativeFormat},
+    image_data::{ImageData, ImageFormat},
+    models::ClipboardItem,
+    options::{SetOptions, KDE_PASSWORD_MANAGER_HINT, KDE_SECRET},
+    snapshot::{ClipboardSnapshot, SnapshotEntry},
+};
+
+/// The MIME type decoded images are snapshotted as, which tells them apart from PNG items.
+const IMAGE_MIME_TYPE: &str = "image/png;x-libclipboard=image";
+/// The MIME type file paths are snapshotted as, which tells them apart from URLs.
+const FILE_LIST_MIME_TYPE: &str = "text/uri-list;x-libclipboard=file-list";
+/// Marks a snapshot of a transient entry, which no Linux convention covers.
+const TRANSIENT_MIME_TYPE: &str = "x-libclipboard-transient";
+
+static NEXT_HANDLE_ID: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    /// Every representation of the current entry, in the order they were published.
+    items: Vec<ClipboardItem>,
+    change_count: u64,
+    owner: Option<usize>,
+    options: SetOptions,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<MemoryState>,
+    /// Notified every time `change_count` is incremented.
+    changed: Condvar,
+}
+
+/// A clipboard that lives entirely inside the current process.
+///
+/// Handles created with [`MemoryClipboard::new_handle`] share the same contents and behave
+/// like separate applications: each one tracks changes on its own and only the handle that
+/// last wrote to the clipboard is its owner.
+#[derive(Debug)]
+pub struct MemoryClipboard {
+    shared: Arc<Shared>,
+    id: usize,
+    last_change_count: AtomicU64,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::with_shared(Arc::default())
+    }
+
+    /// Returns another handle onto the same clipboard.
+    pub fn new_handle(&self) -> Self {
+        Self::with_shared(self.shared.clone())
+    }
+
+    /// Returns `true` if this handle wrote the current clipboard contents.
+    pub fn is_owner(&self) -> bool {
+        self.state().owner == Some(self.id)
+    }
+
+    fn with_shared(shared: Arc<Shared>) -> Self {
+        let change_count = shared.lock().change_count;
+
+        Self {
+            shared,
+            id: NEXT_HANDLE_ID.fetch_add(1, Ordering::Relaxed),
+            last_change_count: AtomicU64::new(change_count),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.shared.lock()
+    }
+
+    fn replace_items(
+        &mut self,
+        items: Vec<ClipboardItem>,
+        owner: Option<usize>,
+        options: SetOptions,
+    ) {
+        let mut state = self.state();
+        state.items = items;
+        state.owner = owner;
+        state.options = options;
+        state.change_count += 1;
+
+        self.shared.changed.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        Ok(self.state().items.first().cloned())
+    }
+
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        Ok(self.state().items.clone())
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        let mut entry: Vec<ClipboardItem> = Vec::with_capacity(items.len());
+
+        for item in items {
+            // The first representation of a format wins, matching the system backends
+            if !entry.iter().any(|existing| existing.kind() == item.kind()) {
+                entry.push(item.clone());
+            }
+        }
+
+        let id = self.id;
+        self.replace_items(entry, Some(id), *options);
+        Ok(())
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        Ok(self.state().options)
+    }
+
+    fn get_item_with_options(&self) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        let state = self.state();
+        Ok(state
+            .items
+            .first()
+            .map(|item| (item.clone(), state.options)))
+    }
+
+    fn get_items_with_options(&self) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        let state = self.state();
+        Ok((state.items.clone(), state.options))
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        let owned = self.is_owner();
+        self.replace_items(Vec::new(), None, SetOptions::default());
+        Ok(owned)
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        Ok(self
+            .state()
+            .items
+            .iter()
+            .map(|item| FormatInfo {
+                kind: item.kind(),
+                native: NativeFormat::MimeType(item.kind().mime_type().unwrap_or_default()),
+                size: Some(payload_size(item)),
+            })
+            .collect())
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        let state = self.state();
+
+        let mut entries = state
+            .items
+            .iter()
+            .map(|item| {
+                let (mime_type, data) = encode_item(item)?;
+
+                Ok(SnapshotEntry {
+                    native: NativeFormat::MimeType(mime_type),
+                    data,
+                })
+            })
+            .collect::<Result<Vec<_>, ClipboardError>>()?;
+
+        // Options are kept the way a Linux clipboard would keep them
+        if state.options.concealed || state.options.exclude_from_history {
+            entries.push(SnapshotEntry {
+                native: NativeFormat::MimeType(KDE_PASSWORD_MANAGER_HINT.to_owned()),
+                data: KDE_SECRET.to_vec(),
+            });
+        }
+
+        if state.options.transient {
+            entries.push(SnapshotEntry {
+                native: NativeFormat::MimeType(TRANSIENT_MIME_TYPE.to_owned()),
+                data: Vec::new(),
+            });
+        }
+
+        Ok(ClipboardSnapshot { entries })
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        let mut items = Vec::with_capacity(snapshot.entries.len());
+        let mut options = SetOptions::default();
+
+        for entry in &snapshot.entries {
+            match &entry.native {
+                NativeFormat::MimeType(mime_type) if mime_type == KDE_PASSWORD_MANAGER_HINT => {
+                    let secret = entry.data == KDE_SECRET;
+                    options.concealed = secret;
+                    options.exclude_from_history = secret;
+                }
+                NativeFormat::MimeType(mime_type) if mime_type == TRANSIENT_MIME_TYPE => {
+                    options.transient = true;
+                }
+                NativeFormat::MimeType(mime_type) => {
+                    items.push(decode_item(mime_type, &entry.data)?)
+                }
+                native => return Err(ClipboardError::UnsupportedFormat(format!("{native:?}"))),
+            }
+        }
+
+        let id = self.id;
+        self.replace_items(items, Some(id), options);
+        Ok(())
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        Ok(self.state().change_count)
+    }
+
+    fn has_changed(&self) -> bool {
+        let change_count = self.state().change_count;
+        self.last_change_count.swap(change_count, Ordering::Relaxed) != change_count
+    }
+
+    fn wait_for_change(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        let deadline = Instant::now() + timeout;
+        let last_change_count = self.last_change_count.load(Ordering::Relaxed);
+        let mut state = self.state();
+
+        while state.change_count == last_change_count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+
+            state = match self.shared.changed.wait_timeout(state, remaining) {
+                Ok((state, _)) => state,
+                Err(e) => e.into_inner().0,
+            };
+        }
+
+        self.last_change_count
+            .store(state.change_count, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    fn factory(&self) -> BackendFactory {
+        let shared = self.shared.clone();
+
+        Arc::new(move || Ok(Box::new(MemoryClipboard::with_shared(shared.clone()))))
+    }
+}
+
+fn payload_size(item: &ClipboardItem) -> usize {
+    match item {
+        ClipboardItem::Html(string)
+        | ClipboardItem::Text(string)
+        | ClipboardItem::UnicodeText(string)
+        | ClipboardItem::Rtf(string)
+        | ClipboardItem::Rtfd(string)
+        | ClipboardItem::Url(string)
+        | ClipboardItem::FilePath(string) => string.len(),
+        ClipboardItem::Png(data) | ClipboardItem::Tiff(data) | ClipboardItem::Pdf(data) => {
+            data.get_ref().len()
+        }
+        ClipboardItem::Image(image) => image.rgba.len(),
+        ClipboardItem::RawBytes(data) => data.len(),
+        ClipboardItem::Custom { data, .. } => data.len(),
+    }
+}
+
+/// Encodes an item the way a MIME-based clipboard would store it.
+fn encode_item(item: &ClipboardItem) -> Result<(String, Vec<u8>), ClipboardError> {
+    let mime_type = match item {
+        ClipboardItem::Image(_) => IMAGE_MIME_TYPE.to_owned(),
+        ClipboardItem::FilePath(_) => FILE_LIST_MIME_TYPE.to_owned(),
+        _ => item.kind().mime_type().unwrap_or_default(),
+    };
+
+    let data = match item {
+        ClipboardItem::Html(string)
+        | ClipboardItem::Text(string)
+        | ClipboardItem::UnicodeText(string)
+        | ClipboardItem::Rtf(string)
+        | ClipboardItem::Rtfd(string)
+        | ClipboardItem::Url(string)
+        | ClipboardItem::FilePath(string) => string.as_bytes().to_vec(),
+        ClipboardItem::Png(data) | ClipboardItem::Tiff(data) | ClipboardItem::Pdf(data) => {
+            data.get_ref().clone()
+        }
+        ClipboardItem::Image(image) => image.encode(ImageFormat::Png)?,
+        ClipboardItem::RawBytes(data) => data.iter().map(|&byte| byte as u8).collect(),
+        ClipboardItem::Custom { data, .. } => data.clone(),
+    };
+
+    Ok((mime_type, data))
+}
+
+fn decode_item(mime_type: &str, data: &[u8]) -> Result<ClipboardItem, ClipboardError> {
+    let string = || {
+        String::from_utf8(data.to_vec())
+            .map_err(|error| ClipboardError::ConversionFailed(error.to_string()))
+    };
+
+    if mime_type == IMAGE_MIME_TYPE {
+        return Ok(ClipboardItem::Image(ImageData::decode(
+            data,
+            ImageFormat::Png,
+        )?));
+    }
+
+    if mime_type == FILE_LIST_MIME_TYPE {
+        return Ok(ClipboardItem::FilePath(string()?));
+    }
+
+    Ok(match FormatKind::from_mime_type(mime_type) {
+        Some(FormatKind::Html) => ClipboardItem::Html(string()?),
+        Some(FormatKind::Text) => ClipboardItem::Text(string()?),
+        Some(FormatKind::UnicodeText) => ClipboardItem::UnicodeText(string()?),
+        Some(FormatKind::Rtf) => ClipboardItem::Rtf(string()?),
+        Some(FormatKind::Rtfd) => ClipboardItem::Rtfd(string()?),
+        Some(FormatKind::Url) => ClipboardItem::Url(string()?),
+        Some(FormatKind::Png) => ClipboardItem::Png(Cursor::new(data.to_vec())),
+        Some(FormatKind::Tiff) => ClipboardItem::Tiff(Cursor::new(data.to_vec())),
+        Some(FormatKind::Pdf) => ClipboardItem::Pdf(Cursor::new(data.to_vec())),
+        Some(FormatKind::RawBytes) => {
+            ClipboardItem::RawBytes(data.iter().map(|&byte| byte as i8).collect())
+        }
+        _ => ClipboardItem::Custom {
+            format: CustomFormat::from_mime_type(mime_type).name,
+            data: data.to_vec(),
+        },
+    })
+}
+
+impl Default for MemoryClipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
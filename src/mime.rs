@@ -0,0 +1,233 @@
+//! Conversions between items and the targets of X11 and Wayland selections.
+//!
+//! Both name their formats with MIME types, alongside a few older X11 names such as
+//! `UTF8_STRING` that Wayland toolkits offer as well, so they share the targets listed in the
+//! format registry.
+
+use std::{char::REPLACEMENT_CHARACTER, io::Cursor};
+
+use crate::{
+    error::ClipboardError,
+    formats::{CustomFormat, FormatKind},
+    image_data::ImageFormat,
+    models::ClipboardItem,
+};
+
+const URI_LIST: &str = "text/uri-list";
+/// A URL and its title on separate lines, in UTF-16.
+const MOZ_URL: &str = "text/x-moz-url";
+/// A URI list preceded by a line with `copy` or `cut`, used by file managers.
+const GNOME_COPIED_FILES: &str = "x-special/gnome-copied-files";
+
+/// Returns the kind of data a target holds.
+pub(crate) fn target_kind(target: &str) -> FormatKind {
+    FormatKind::from_x11_target(target)
+        .or_else(|| FormatKind::from_mime_type(target))
+        .unwrap_or_else(|| {
+            // Anything that looks like a MIME type is passed through as a custom format
+            if target.contains('/') {
+                FormatKind::Custom(CustomFormat::from_mime_type(target).name)
+            } else {
+                FormatKind::Unknown
+            }
+        })
+}
+
+/// Returns the kinds a target can hold, which are several for a URI list.
+fn target_kinds(target: &str) -> Vec<FormatKind> {
+    match target {
+        URI_LIST => vec![FormatKind::Url, FormatKind::FileList],
+        target => vec![target_kind(target)],
+    }
+}
+
+/// Returns whether `target` can hold `kind`.
+pub(crate) fn holds(target: &str, kind: &FormatKind) -> bool {
+    target_kinds(target).contains(kind)
+}
+
+/// Ranks `target` among the targets that hold `kind`, lower ranks being preferred.
+pub(crate) fn preference(kind: &FormatKind, target: &str) -> usize {
+    let preferred = kind
+        .identifiers()
+        .map_or(&[][..], |identifiers| identifiers.x11_targets);
+
+    preferred
+        .iter()
+        .position(|preferred| *preferred == target)
+        .unwrap_or(preferred.len())
+}
+
+/// Returns the kinds that `targets` hold, in the order they are offered.
+pub(crate) fn offered_kinds<'a>(targets: impl IntoIterator<Item = &'a str>) -> Vec<FormatKind> {
+    let mut kinds = Vec::new();
+
+    for kind in targets.into_iter().flat_map(target_kinds) {
+        if kind != FormatKind::Unknown && !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+
+    kinds
+}
+
+/// Returns the targets an item is written as, starting with the preferred one.
+pub(crate) fn item_targets(item: &ClipboardItem) -> Vec<String> {
+    let kind = item.kind();
+
+    match kind.identifiers() {
+        Some(identifiers) => identifiers
+            .x11_targets
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        None => kind.mime_type().into_iter().collect(),
+    }
+}
+
+pub(crate) fn decode_item(target: &str, data: Vec<u8>) -> Option<ClipboardItem> {
+    Some(match target_kind(target) {
+        FormatKind::Html => ClipboardItem::Html(decode_text(target, data)),
+        FormatKind::Text => ClipboardItem::Text(decode_text(target, data)),
+        FormatKind::UnicodeText => ClipboardItem::UnicodeText(decode_text(target, data)),
+        FormatKind::Rtf => ClipboardItem::Rtf(decode_text(target, data)),
+        FormatKind::Rtfd => ClipboardItem::Rtfd(decode_text(target, data)),
+        FormatKind::Url | FormatKind::FileList => {
+            decode_uri_list(target, &decode_text(target, data))?
+        }
+        FormatKind::Png => ClipboardItem::Png(Cursor::new(data)),
+        FormatKind::Tiff => ClipboardItem::Tiff(Cursor::new(data)),
+        FormatKind::Pdf => ClipboardItem::Pdf(Cursor::new(data)),
+        FormatKind::RawBytes => {
+            ClipboardItem::RawBytes(data.into_iter().map(|byte| byte as i8).collect())
+        }
+        FormatKind::Custom(format) => ClipboardItem::Custom { format, data },
+        _ => return None,
+    })
+}
+
+pub(crate) fn encode_item(item: &ClipboardItem, target: &str) -> Result<Vec<u8>, ClipboardError> {
+    Ok(match item {
+        ClipboardItem::Text(text) | ClipboardItem::UnicodeText(text)
+            if matches!(target, "STRING" | "TEXT") =>
+        {
+            encode_latin1(text)
+        }
+        ClipboardItem::Html(string)
+        | ClipboardItem::Text(string)
+        | ClipboardItem::UnicodeText(string)
+        | ClipboardItem::Rtf(string)
+        | ClipboardItem::Rtfd(string) => string.as_bytes().to_vec(),
+        ClipboardItem::Url(url) if target == MOZ_URL => {
+            url.encode_utf16().flat_map(u16::to_le_bytes).collect()
+        }
+        ClipboardItem::Url(url) => url.as_bytes().to_vec(),
+        ClipboardItem::FilePath(path) if target == GNOME_COPIED_FILES => {
+            format!("copy\n{}", file_uri(path)).into_bytes()
+        }
+        ClipboardItem::FilePath(path) => file_uri(path).into_bytes(),
+        ClipboardItem::Png(data) | ClipboardItem::Tiff(data) | ClipboardItem::Pdf(data) => {
+            data.get_ref().clone()
+        }
+        ClipboardItem::Image(image) => image.encode(ImageFormat::Png)?,
+        ClipboardItem::RawBytes(data) => data.iter().map(|&byte| byte as u8).collect(),
+        ClipboardItem::Custom { data, .. } => data.clone(),
+    })
+}
+
+/// Decodes text, which is Latin-1 for `STRING` and `TEXT`, UTF-16 for the targets browsers
+/// write that way and UTF-8 for everything else.
+fn decode_text(target: &str, data: Vec<u8>) -> String {
+    let text = match data.as_slice() {
+        latin1 if matches!(target, "STRING" | "TEXT") => {
+            latin1.iter().map(|&byte| byte as char).collect()
+        }
+        [0xff, 0xfe, utf16 @ ..] => decode_utf16(utf16),
+        utf16 if target == MOZ_URL => decode_utf16(utf16),
+        utf8 => String::from_utf8_lossy(utf8).into_owned(),
+    };
+
+    text.trim_end_matches('\0').to_owned()
+}
+
+fn decode_utf16(data: &[u8]) -> String {
+    let units = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// Decodes the first entry of a URI list, as a path if it is a local file.
+fn decode_uri_list(target: &str, list: &str) -> Option<ClipboardItem> {
+    let mut uris = list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    if target == GNOME_COPIED_FILES {
+        uris.next();
+    }
+
+    let uri = uris.next()?;
+
+    Some(match file_path(uri) {
+        Some(path) => ClipboardItem::FilePath(path),
+        None => ClipboardItem::Url(uri.to_owned()),
+    })
+}
+
+/// Returns the path of a `file://` URI on this machine.
+fn file_path(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("file://")?;
+    let path = match rest.strip_prefix("localhost") {
+        Some(path) => path,
+        None if rest.starts_with('/') => rest,
+        None => return None,
+    };
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(escaped) if byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn file_uri(path: &str) -> String {
+    let mut uri = String::from("file://");
+
+    for &byte in path.as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    uri
+}
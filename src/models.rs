@@ -1,113 +1,360 @@
-use std::io::Cursor;
+use std::{fmt, io::Cursor, time::Duration};
 
 use cfg_if::cfg_if;
 
 #[cfg(target_os = "macos")]
 use crate::macos::MacOSCC;
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::wayland::WaylandCC;
 #[cfg(target_os = "windows")]
 use crate::windows::WindowsCC;
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::x11::X11CC;
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    convert,
+    error::ClipboardError,
+    expiry::ExpiryHandle,
+    formats::{FormatInfo, FormatKind},
+    image_data::ImageData,
+    memory::MemoryClipboard,
+    options::{GetOptions, SetOptions, WriteOptions},
+    snapshot::ClipboardSnapshot,
+    watcher::ClipboardWatcher,
+};
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum Clipboard {
-    #[cfg(target_os = "windows")]
-    Windows(WindowsCC),
-    #[cfg(target_os = "macos")]
-    MacOS(MacOSCC),
+/// Which of the system's clipboards or selections to use.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ClipboardKind {
+    /// The regular clipboard used by copy and paste.
+    #[default]
+    Clipboard,
+    /// The X11 or Wayland primary selection, set by selecting text.
+    Primary,
+    /// The X11 secondary selection.
+    Secondary,
+    /// The macOS find pasteboard, shared by the search fields of all applications.
+    Find,
+    /// A macOS pasteboard with the given name.
+    Named(String),
 }
 
-impl Clipboard {
-    pub fn new() -> Result<Self, &'static str> {
-        cfg_if! {
-            if #[cfg(target_os = "windows")] {
-                Ok(Clipboard::Windows(WindowsCC::new()?))
-            } else if #[cfg(target_os = "macos")] {
-                Ok(Clipboard::MacOS(MacOSCC::new()))
-            } else {
-                Err("Does not support this OS")
-            }
+impl fmt::Display for ClipboardKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Clipboard => write!(f, "clipboard"),
+            Self::Primary => write!(f, "primary selection"),
+            Self::Secondary => write!(f, "secondary selection"),
+            Self::Find => write!(f, "find pasteboard"),
+            Self::Named(name) => write!(f, "pasteboard \"{name}\""),
         }
     }
+}
+
+#[derive(Debug)]
+pub struct Clipboard {
+    backend: Box<dyn ClipboardBackend>,
+}
+
+impl Clipboard {
+    pub fn new() -> Result<Self, ClipboardError> {
+        Self::with_kind(ClipboardKind::Clipboard)
+    }
 
-    pub fn get_item(&self) -> Option<ClipboardItem> {
+    /// Opens one of the system's clipboards or selections.
+    ///
+    /// Returns [`ClipboardError::UnsupportedKind`] if the kind doesn't exist on this platform.
+    pub fn with_kind(kind: ClipboardKind) -> Result<Self, ClipboardError> {
         cfg_if! {
             if #[cfg(target_os = "windows")] {
-                match self {
-                    Clipboard::Windows(cc) => cc.get_clipboard_item(),
+                match kind {
+                    ClipboardKind::Clipboard => Ok(Self::from_backend(WindowsCC::new()?)),
+                    kind => Err(ClipboardError::UnsupportedKind(kind)),
                 }
             } else if #[cfg(target_os = "macos")] {
-                match self {
-                    Clipboard::MacOS(cc) => cc.get_clipboard_item(),
+                Ok(Self::from_backend(MacOSCC::with_kind(kind)?))
+            } else if #[cfg(unix)] {
+                // Native Wayland clients only share their data through Wayland, so it is
+                // preferred over Xwayland, unless the compositor only gives access to the
+                // focused client, as GNOME does
+                if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                    if let Ok(clipboard) = WaylandCC::with_kind(kind.clone()) {
+                        if clipboard.has_background_access() {
+                            return Ok(Self::from_backend(clipboard));
+                        }
+                    }
                 }
+
+                Ok(Self::from_backend(X11CC::with_kind(kind)?))
             } else {
-                None
+                let _ = kind;
+
+                Err(ClipboardError::BackendUnavailable(
+                    "this OS is not supported".to_owned(),
+                ))
             }
         }
     }
 
-    pub fn get_items(&self) -> Option<Vec<ClipboardItem>> {
-        cfg_if! {
-            if #[cfg(target_os = "windows")] {
-                match self {
-                    Clipboard::Windows(cc) => cc.get_clipboard_items(),
-                }
-            } else if #[cfg(target_os = "macos")] {
-                match self {
-                    Clipboard::MacOS(cc) => cc.get_clipboard_items(),
-                }
-            } else {
-                None
-            }
+    /// Creates a clipboard backed by a fresh in-process [`MemoryClipboard`].
+    ///
+    /// Useful for tests and headless environments where no system clipboard is available.
+    pub fn in_memory() -> Self {
+        Self::from_backend(MemoryClipboard::new())
+    }
+
+    /// Creates a clipboard that delegates to the given backend.
+    pub fn from_backend<B: ClipboardBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Box::new(backend),
         }
     }
 
-    pub fn set_item(&mut self, item: ClipboardItem) {
-        cfg_if! {
-            if #[cfg(target_os = "windows")] {
-                match self {
-                    Clipboard::Windows(cc) => cc.set_clipboard_item(item),
-                }
-            } else if #[cfg(target_os = "macos")] {
-                match self {
-                    Clipboard::MacOS(cc) => cc.set_clipboard_item(item),
-                }
-            }
+    /// Returns which clipboard or selection this clipboard reads and writes.
+    pub fn kind(&self) -> ClipboardKind {
+        self.backend.kind()
+    }
+
+    pub fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.backend.get_item()
+    }
+
+    /// Returns the first item on the clipboard together with the options it was written with,
+    /// so that a clipboard history can leave out concealed and transient items.
+    pub fn get_item_with_options(
+        &self,
+    ) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        self.backend.get_item_with_options()
+    }
+
+    /// Returns every item on the clipboard together with the options they were written with.
+    pub fn get_items_with_options(
+        &self,
+    ) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        self.backend.get_items_with_options()
+    }
+
+    /// Returns the options the current contents were written with.
+    pub fn options(&self) -> Result<SetOptions, ClipboardError> {
+        self.backend.options()
+    }
+
+    pub fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        self.backend.get_items()
+    }
+
+    /// Returns the item of the given kind, synthesizing it from another representation if the
+    /// clipboard doesn't have it, such as plain text from HTML.
+    pub fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_format_with_options(kind, &GetOptions::default())
+    }
+
+    /// Like [`Clipboard::get_format`], but only synthesizes missing items if `options` allow it.
+    pub fn get_format_with_options(
+        &self,
+        kind: &FormatKind,
+        options: &GetOptions,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        if let Some(item) = self.backend.get_format(kind)? {
+            return Ok(Some(item));
+        }
+
+        if !options.synthesize {
+            return Ok(None);
         }
+
+        self.synthesize(kind, &self.available_formats()?)
     }
 
-    pub fn number_of_formats(&self) -> i32 {
-        cfg_if! {
-            if #[cfg(target_os = "windows")] {
-                match self {
-                    Clipboard::Windows(cc) => cc.get_number_of_formats(),
-                }
-            } else if #[cfg(target_os = "macos")] {
-                match self {
-                    Clipboard::MacOS(cc) => cc.get_number_of_formats(),
-                }
-            } else {
-                0
+    /// Returns the item matching the earliest entry of `preferences` that is on the clipboard.
+    ///
+    /// If none of them is, the earliest entry that can be synthesized from another
+    /// representation is returned. If `preferences` is empty, [`FormatKind::FIDELITY_ORDER`] is
+    /// used.
+    pub fn get_preferred(
+        &self,
+        preferences: &[FormatKind],
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_preferred_with_options(preferences, &GetOptions::default())
+    }
+
+    /// Like [`Clipboard::get_preferred`], but only synthesizes missing items if `options` allow
+    /// it.
+    pub fn get_preferred_with_options(
+        &self,
+        preferences: &[FormatKind],
+        options: &GetOptions,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let preferences = match preferences {
+            [] => FormatKind::FIDELITY_ORDER,
+            preferences => preferences,
+        };
+
+        let available = self.available_formats()?;
+
+        for kind in preferences {
+            if !available.iter().any(|format| format.kind == *kind) {
+                continue;
+            }
+
+            if let Some(item) = self.backend.get_format(kind)? {
+                return Ok(Some(item));
+            }
+        }
+
+        if !options.synthesize {
+            return Ok(None);
+        }
+
+        for kind in preferences {
+            if let Some(item) = self.synthesize(kind, &available)? {
+                return Ok(Some(item));
             }
         }
+
+        Ok(None)
     }
 
-    pub fn has_changed(&self) -> bool {
-        cfg_if! {
-            if #[cfg(target_os = "macos")] {
-                match self {
-                    Clipboard::MacOS(cc) => cc.has_clipboard_changed(),
-                }
-            } else if #[cfg(target_os = "windows")] {
-                match self {
-                    Clipboard::Windows(cc) => cc.has_clipboard_changed(),
+    /// Converts the first available representation that `kind` can be synthesized from.
+    fn synthesize(
+        &self,
+        kind: &FormatKind,
+        available: &[FormatInfo],
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        for source in convert::read_sources(kind) {
+            if !available.iter().any(|format| format.kind == *source) {
+                continue;
+            }
+
+            if let Some(item) = self.backend.get_format(source)? {
+                if let Some(item) = item.convert(kind)? {
+                    return Ok(Some(item));
                 }
-            } else {
-                false
             }
         }
+
+        Ok(None)
+    }
+
+    /// Writes `item`, along with the fallback representations described in
+    /// [`Clipboard::set_items`].
+    pub fn set_item(&mut self, item: ClipboardItem) -> Result<(), ClipboardError> {
+        self.set_items_with_options(&[item], &SetOptions::default())
+    }
+
+    /// Publishes every item in `items` as a representation of the same content, so that paste
+    /// targets can pick the richest format they understand.
+    ///
+    /// Plain text is added if `items` have none but include HTML, RTF, a URL or a file, and a
+    /// PNG is added next to a TIFF, so that every application can paste something.
+    pub fn set_items(&mut self, items: &[ClipboardItem]) -> Result<(), ClipboardError> {
+        self.set_items_with_options(items, &SetOptions::default())
+    }
+
+    /// Like [`Clipboard::set_item`], but also tells clipboard managers how to treat the item.
+    pub fn set_item_with_options(
+        &mut self,
+        item: ClipboardItem,
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.set_items_with_options(&[item], options)
+    }
+
+    /// Like [`Clipboard::set_items`], but also tells clipboard managers how to treat the items.
+    pub fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.set_items_with_write_options(items, options, &WriteOptions::default())
+    }
+
+    /// Like [`Clipboard::set_items_with_options`], but only adds fallback representations if
+    /// `write_options` allow it.
+    pub fn set_items_with_write_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+        write_options: &WriteOptions,
+    ) -> Result<(), ClipboardError> {
+        if write_options.fallbacks {
+            self.backend
+                .set_items_with_options(&convert::with_fallbacks(items)?, options)
+        } else {
+            self.backend.set_items_with_options(items, options)
+        }
+    }
+
+    /// Writes `item` and clears the clipboard again after `timeout`, unless something else has
+    /// been written to it in the meantime.
+    ///
+    /// The clear runs on a background thread with a backend of its own, so it still happens
+    /// if this clipboard is dropped, but not if the process exits first.
+    pub fn set_item_with_expiry(
+        &mut self,
+        item: ClipboardItem,
+        timeout: Duration,
+    ) -> Result<ExpiryHandle, ClipboardError> {
+        self.set_item(item)?;
+
+        ExpiryHandle::spawn(self.factory(), self.backend.change_count()?, timeout)
+    }
+
+    /// Empties the clipboard, returning `true` if its contents were written by this clipboard.
+    pub fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.backend.clear()
+    }
+
+    /// Lists the formats currently on the clipboard without fetching their data.
+    pub fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        self.backend.available_formats()
+    }
+
+    /// Captures every format on the clipboard so it can be put back later with
+    /// [`Clipboard::restore`].
+    pub fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        self.backend.snapshot()
+    }
+
+    /// Replaces the contents of the clipboard with a snapshot taken earlier.
+    pub fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.backend.restore(snapshot)
+    }
+
+    /// Returns a number that increases every time the clipboard contents change.
+    ///
+    /// Unlike [`Clipboard::has_changed`] this doesn't consume anything, so any number of callers
+    /// can remember the last value they saw and compare it later.
+    pub fn change_count(&self) -> Result<u64, ClipboardError> {
+        self.backend.change_count()
+    }
+
+    pub fn has_changed(&self) -> bool {
+        self.backend.has_changed()
+    }
+
+    /// Starts watching this clipboard for changes on a separate thread.
+    pub fn watch(&self) -> Result<ClipboardWatcher, ClipboardError> {
+        ClipboardWatcher::new(self.factory())
+    }
+
+    /// Returns a factory that opens new backends onto the same clipboard, for use on other
+    /// threads.
+    pub fn factory(&self) -> BackendFactory {
+        self.backend.factory()
+    }
+}
+
+impl From<Box<dyn ClipboardBackend>> for Clipboard {
+    fn from(backend: Box<dyn ClipboardBackend>) -> Self {
+        Self { backend }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClipboardItem {
     Html(String),
     Text(String),
@@ -116,8 +363,61 @@ pub enum ClipboardItem {
     Rtfd(String),
     Url(String),
     FilePath(String),
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cursor"))]
     Png(Cursor<Vec<u8>>),
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cursor"))]
     Tiff(Cursor<Vec<u8>>),
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::cursor"))]
     Pdf(Cursor<Vec<u8>>),
+    /// Decoded pixels, written in whichever image format the platform's applications read.
+    Image(ImageData),
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::signed_bytes"))]
     RawBytes(Vec<i8>),
+    /// Data in an application-defined format, identified by the name of a [`CustomFormat`].
+    ///
+    /// [`CustomFormat`]: crate::CustomFormat
+    Custom {
+        format: String,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::bytes"))]
+        data: Vec<u8>,
+    },
+}
+
+impl ClipboardItem {
+    pub fn kind(&self) -> FormatKind {
+        match self {
+            Self::Html(_) => FormatKind::Html,
+            Self::Text(_) => FormatKind::Text,
+            Self::UnicodeText(_) => FormatKind::UnicodeText,
+            Self::Rtf(_) => FormatKind::Rtf,
+            Self::Rtfd(_) => FormatKind::Rtfd,
+            Self::Url(_) => FormatKind::Url,
+            Self::FilePath(_) => FormatKind::FileList,
+            Self::Png(_) => FormatKind::Png,
+            Self::Tiff(_) => FormatKind::Tiff,
+            Self::Pdf(_) => FormatKind::Pdf,
+            Self::Image(_) => FormatKind::Image,
+            Self::RawBytes(_) => FormatKind::RawBytes,
+            Self::Custom { format, .. } => FormatKind::Custom(format.clone()),
+        }
+    }
+
+    /// Returns the name of the variant, e.g. `"UnicodeText"`.
+    pub fn format_name(&self) -> &'static str {
+        match self {
+            Self::Html(_) => "Html",
+            Self::Text(_) => "Text",
+            Self::UnicodeText(_) => "UnicodeText",
+            Self::Rtf(_) => "Rtf",
+            Self::Rtfd(_) => "Rtfd",
+            Self::Url(_) => "Url",
+            Self::FilePath(_) => "FilePath",
+            Self::Png(_) => "Png",
+            Self::Tiff(_) => "Tiff",
+            Self::Pdf(_) => "Pdf",
+            Self::Image(_) => "Image",
+            Self::RawBytes(_) => "RawBytes",
+            Self::Custom { .. } => "Custom",
+        }
+    }
 }
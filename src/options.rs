@@ -0,0 +1,61 @@
+/// Tells clipboard managers and other observers how to treat data being written.
+///
+/// Each option is written using the conventions of the platform, where one exists:
+///
+/// - `concealed`: `org.nspasteboard.ConcealedType` on macOS,
+///   `ExcludeClipboardContentFromMonitorProcessing` on Windows and `x-kde-passwordManagerHint`
+///   on Linux.
+/// - `transient`: `org.nspasteboard.TransientType` on macOS.
+/// - `exclude_from_history`: `org.nspasteboard.TransientType` on macOS,
+///   `CanIncludeInClipboardHistory` and `CanUploadToCloudClipboard` on Windows and
+///   `x-kde-passwordManagerHint` on Linux.
+///
+/// Reading the options back is only as precise as the conventions: a transient entry on macOS is
+/// also reported as excluded from history, and on Linux the two options that share a marker are
+/// always reported together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SetOptions {
+    /// The data is sensitive, such as a password, and should not be shown or recorded.
+    pub concealed: bool,
+    /// The data is only on the clipboard briefly, for example while it is being pasted into
+    /// another application.
+    pub transient: bool,
+    /// The data should not be added to clipboard history or synced to other devices.
+    pub exclude_from_history: bool,
+}
+
+/// Controls how items are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GetOptions {
+    /// Converts another representation when the requested format is missing, such as HTML into
+    /// plain text or TIFF into PNG. Enabled by default.
+    pub synthesize: bool,
+}
+
+impl Default for GetOptions {
+    fn default() -> Self {
+        Self { synthesize: true }
+    }
+}
+
+/// Controls how items are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteOptions {
+    /// Adds the plain text and PNG representations that rich content lacks, as described in
+    /// [`Clipboard::set_items`](crate::Clipboard::set_items). Enabled by default.
+    pub fallbacks: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self { fallbacks: true }
+    }
+}
+
+/// The MIME type KDE's clipboard manager checks to leave passwords out of its history.
+pub(crate) const KDE_PASSWORD_MANAGER_HINT: &str = "x-kde-passwordManagerHint";
+/// The value of [`KDE_PASSWORD_MANAGER_HINT`] that marks the data as a secret.
+pub(crate) const KDE_SECRET: &[u8] = b"secret";
@@ -0,0 +1,110 @@
+//! `#[serde(with = ...)]` helpers for binary payloads.
+//!
+//! Human-readable formats such as JSON get a base64 string, binary formats get the raw bytes.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserializer, Serializer,
+};
+
+fn serialize_bytes<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    } else {
+        serializer.serialize_bytes(bytes)
+    }
+}
+
+fn deserialize_bytes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_str(BytesVisitor)
+    } else {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a base64 string or a byte array")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Vec<u8>, E> {
+        STANDARD.decode(value).map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(value.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(value)
+    }
+
+    // Formats without a native byte type hand the bytes over as a sequence
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+
+        Ok(bytes)
+    }
+}
+
+pub(crate) mod bytes {
+    use serde::{Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        super::serialize_bytes(bytes, serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        super::deserialize_bytes(deserializer)
+    }
+}
+
+pub(crate) mod cursor {
+    use std::io::Cursor;
+
+    use serde::{Deserializer, Serializer};
+
+    /// Only the contents are kept; the position isn't part of the clipboard data.
+    pub(crate) fn serialize<S: Serializer>(
+        cursor: &Cursor<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_bytes(cursor.get_ref(), serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Cursor<Vec<u8>>, D::Error> {
+        super::deserialize_bytes(deserializer).map(Cursor::new)
+    }
+}
+
+pub(crate) mod signed_bytes {
+    use serde::{Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(bytes: &[i8], serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = bytes.iter().map(|&byte| byte as u8).collect();
+        super::serialize_bytes(&bytes, serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<i8>, D::Error> {
+        let bytes = super::deserialize_bytes(deserializer)?;
+        Ok(bytes.into_iter().map(|byte| byte as i8).collect())
+    }
+}
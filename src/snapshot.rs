@@ -0,0 +1,29 @@
+use crate::formats::NativeFormat;
+
+/// The raw contents of every format that was on the clipboard at some point in time.
+///
+/// Snapshots are taken with [`Clipboard::snapshot`](crate::Clipboard::snapshot) and put back
+/// with [`Clipboard::restore`](crate::Clipboard::restore). They hold native identifiers, so
+/// they can only be restored by the same kind of backend that took them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClipboardSnapshot {
+    /// The formats in the order the clipboard reported them.
+    pub entries: Vec<SnapshotEntry>,
+}
+
+impl ClipboardSnapshot {
+    /// Returns `true` if the clipboard was empty when the snapshot was taken.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// A single format stored in a [`ClipboardSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEntry {
+    pub native: NativeFormat,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::bytes"))]
+    pub data: Vec<u8>,
+}
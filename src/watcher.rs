@@ -0,0 +1,213 @@
+use std::{
+    fmt, mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::{backend::BackendFactory, error::ClipboardError, models::ClipboardKind};
+
+/// How long the listener thread waits for a change before checking whether it should stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Describes a change of the clipboard contents observed by a [`ClipboardWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClipboardChange {
+    /// The clipboard or selection that changed.
+    pub kind: ClipboardKind,
+    /// When the change was observed.
+    pub timestamp: SystemTime,
+    /// The [change count](crate::Clipboard::change_count) right after the change, if the
+    /// backend could report it.
+    pub change_count: Option<u64>,
+    /// What the platform reported about the latest change, if the backend receives change
+    /// events from it.
+    pub native: Option<NativeChange>,
+}
+
+/// The details of a clipboard change as reported by the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NativeChange {
+    /// The window that owns the clipboard after the change, or `None` if nothing owns it.
+    pub owner: Option<u64>,
+    /// When the change happened in the platform's clock, such as the X server time in
+    /// milliseconds.
+    pub timestamp: u64,
+}
+
+enum Subscriber {
+    Callback(Box<dyn FnMut(&ClipboardChange) + Send>),
+    Channel(Sender<ClipboardChange>),
+    #[cfg(feature = "async")]
+    Stream(futures::channel::mpsc::UnboundedSender<ClipboardChange>),
+}
+
+impl Subscriber {
+    /// Delivers the change, returning `false` if the subscriber is gone.
+    fn notify(&mut self, change: &ClipboardChange) -> bool {
+        match self {
+            Self::Callback(callback) => {
+                callback(change);
+                true
+            }
+            Self::Channel(sender) => sender.send(change.clone()).is_ok(),
+            #[cfg(feature = "async")]
+            Self::Stream(sender) => sender.unbounded_send(change.clone()).is_ok(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    subscribers: Mutex<Vec<Subscriber>>,
+    stop: AtomicBool,
+}
+
+impl Shared {
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Subscriber>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Delivers `change` to every subscriber without holding the lock, so callbacks may
+    /// subscribe to the watcher themselves and don't hold up new subscriptions.
+    fn notify(&self, change: &ClipboardChange) {
+        let mut subscribers = mem::take(&mut *self.subscribers());
+        subscribers.retain_mut(|subscriber| subscriber.notify(change));
+
+        // Keeps subscribers added in the meantime after the existing ones
+        let mut current = self.subscribers();
+        subscribers.append(&mut current);
+        *current = subscribers;
+    }
+}
+
+/// Watches a clipboard for changes on a listener thread of its own.
+///
+/// Every subscriber receives every change, independently of other watchers and of
+/// [`Clipboard::has_changed`](crate::Clipboard::has_changed). The listener thread is stopped
+/// when the watcher is dropped.
+pub struct ClipboardWatcher {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ClipboardWatcher {
+    /// Starts watching the clipboard opened by `factory`.
+    ///
+    /// The backend is opened on the listener thread; an error opening it is returned here.
+    pub fn new(factory: BackendFactory) -> Result<Self, ClipboardError> {
+        let shared = Arc::new(Shared::default());
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        let thread = {
+            let shared = shared.clone();
+
+            thread::Builder::new()
+                .name("libclipboard-watcher".to_owned())
+                .spawn(move || {
+                    let backend = match factory() {
+                        Ok(backend) => backend,
+                        Err(error) => {
+                            let _ = ready_tx.send(Err(error));
+                            return;
+                        }
+                    };
+
+                    // Only changes made after the watcher started are reported
+                    backend.has_changed();
+                    let _ = ready_tx.send(Ok(()));
+
+                    while !shared.stop.load(Ordering::Relaxed) {
+                        match backend.wait_for_change(STOP_POLL_INTERVAL) {
+                            Ok(true) => {
+                                let change = ClipboardChange {
+                                    kind: backend.kind(),
+                                    timestamp: SystemTime::now(),
+                                    change_count: backend.change_count().ok(),
+                                    native: backend.last_native_change(),
+                                };
+
+                                shared.notify(&change);
+                            }
+                            Ok(false) => {}
+                            // The clipboard can be temporarily unavailable; try again later
+                            Err(_) => thread::sleep(STOP_POLL_INTERVAL),
+                        }
+                    }
+                })
+                .map_err(|error| ClipboardError::BackendUnavailable(error.to_string()))?
+        };
+
+        let watcher = Self {
+            shared,
+            thread: Some(thread),
+        };
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(watcher),
+            Ok(Err(error)) => Err(error),
+            Err(_) => Err(ClipboardError::BackendUnavailable(
+                "the watcher thread exited unexpectedly".to_owned(),
+            )),
+        }
+    }
+
+    /// Returns a channel that receives every change from now on.
+    ///
+    /// Dropping the receiver unsubscribes it.
+    pub fn subscribe(&self) -> Receiver<ClipboardChange> {
+        let (sender, receiver) = mpsc::channel();
+        self.shared.subscribers().push(Subscriber::Channel(sender));
+        receiver
+    }
+
+    /// Returns an async channel that receives every change from now on.
+    #[cfg(feature = "async")]
+    pub(crate) fn subscribe_unbounded(
+        &self,
+    ) -> futures::channel::mpsc::UnboundedReceiver<ClipboardChange> {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        self.shared.subscribers().push(Subscriber::Stream(sender));
+        receiver
+    }
+
+    /// Calls `callback` on the listener thread for every change from now on.
+    pub fn on_change(&self, callback: impl FnMut(&ClipboardChange) + Send + 'static) {
+        self.shared
+            .subscribers()
+            .push(Subscriber::Callback(Box::new(callback)));
+    }
+
+    /// Stops the listener thread and waits for it to exit.
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for ClipboardWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClipboardWatcher")
+            .field("subscribers", &self.shared.subscribers().len())
+            .field("running", &self.thread.is_some())
+            .finish()
+    }
+}
+
+impl Drop for ClipboardWatcher {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
@@ -0,0 +1,1241 @@
+use std::{
+    cell::Cell,
+    fmt,
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::{
+        fd::{AsFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use wayland_client::{
+    backend::WaylandError,
+    delegate_noop, event_created_child,
+    globals::{registry_queue_init, BindError, GlobalError, GlobalList, GlobalListContents},
+    protocol::{
+        wl_callback::{self, WlCallback},
+        wl_data_device::{self, WlDataDevice},
+        wl_data_device_manager::WlDataDeviceManager,
+        wl_data_offer::{self, WlDataOffer},
+        wl_data_source::{self, WlDataSource},
+        wl_keyboard::{self, WlKeyboard},
+        wl_registry::WlRegistry,
+        wl_seat::{self, WlSeat},
+    },
+    ConnectError, Connection, Dispatch, DispatchError, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols::ext::data_control::v1::client::{
+    ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+    ext_data_control_manager_v1::ExtDataControlManagerV1,
+    ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+    ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+};
+use wayland_protocols::wp::primary_selection::zv1::client::{
+    zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+    zwp_primary_selection_device_v1::{self, ZwpPrimarySelectionDeviceV1},
+    zwp_primary_selection_offer_v1::{self, ZwpPrimarySelectionOfferV1},
+    zwp_primary_selection_source_v1::{self, ZwpPrimarySelectionSourceV1},
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    error::ClipboardError,
+    formats::{FormatInfo, FormatKind, NativeFormat},
+    mime,
+    models::{ClipboardItem, ClipboardKind},
+    options::{SetOptions, KDE_PASSWORD_MANAGER_HINT, KDE_SECRET},
+    snapshot::{ClipboardSnapshot, SnapshotEntry},
+};
+
+/// How long the source of a selection has to start sending data, and the longest pause while
+/// it sends.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The data offered for a selection, as MIME type and data.
+type Contents = Vec<(String, Vec<u8>)>;
+
+/// The MIME types of an offer, which the compositor announces one by one.
+type MimeTypes = Mutex<Vec<String>>;
+
+/// The server of the current process, if it has been started.
+static SERVER: Mutex<Option<Arc<Server>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Clipboard,
+    /// The selection pasted with the middle mouse button.
+    Primary,
+}
+
+/// How the primary selection is accessed.
+enum Primary {
+    /// Through the data control device, which serves the clipboard too.
+    DataControl,
+    /// Through `zwp_primary_selection_device_manager_v1`, which only gives access with keyboard
+    /// focus.
+    Zwp(
+        ZwpPrimarySelectionDeviceManagerV1,
+        ZwpPrimarySelectionDeviceV1,
+    ),
+}
+
+/// The protocol the clipboard is accessed through.
+enum Manager {
+    /// `ext-data-control-v1`, which gives access to the selection without keyboard focus.
+    Ext(ExtDataControlManagerV1),
+    /// `wlr-data-control-unstable-v1`, the predecessor of `ext-data-control-v1` in wlroots-based
+    /// compositors.
+    Wlr(ZwlrDataControlManagerV1),
+    /// The core `wl_data_device_manager`, which only gives access with keyboard focus.
+    Core(WlDataDeviceManager),
+}
+
+impl Manager {
+    /// Binds the first protocol the compositor offers, preferring data control.
+    fn bind(globals: &GlobalList, qh: &QueueHandle<Dispatcher>) -> Result<Self, BindError> {
+        if let Ok(manager) = globals.bind(qh, 1..=1, ()) {
+            return Ok(Self::Ext(manager));
+        }
+
+        // Version 2 adds the primary selection
+        if let Ok(manager) = globals.bind(qh, 1..=2, ()) {
+            return Ok(Self::Wlr(manager));
+        }
+
+        Ok(Self::Core(globals.bind(qh, 1..=3, ())?))
+    }
+
+    fn get_data_device(&self, seat: &WlSeat, qh: &QueueHandle<Dispatcher>) -> Device {
+        match self {
+            Self::Ext(manager) => Device::Ext(manager.get_data_device(seat, qh, ())),
+            Self::Wlr(manager) => Device::Wlr(manager.get_data_device(seat, qh, ())),
+            Self::Core(manager) => Device::Core(manager.get_data_device(seat, qh, ())),
+        }
+    }
+
+    fn create_data_source(&self, qh: &QueueHandle<Dispatcher>, contents: Contents) -> Source {
+        match self {
+            Self::Ext(manager) => Source::Ext(manager.create_data_source(qh, contents)),
+            Self::Wlr(manager) => Source::Wlr(manager.create_data_source(qh, contents)),
+            Self::Core(manager) => Source::Core(manager.create_data_source(qh, contents)),
+        }
+    }
+}
+
+enum Device {
+    Ext(ExtDataControlDeviceV1),
+    Wlr(ZwlrDataControlDeviceV1),
+    Core(WlDataDevice),
+}
+
+impl Device {
+    /// Sets or clears the selection. Only the core protocol checks `serial`.
+    fn set_selection(&self, source: Option<&Source>, serial: u32) {
+        match (self, source) {
+            (Self::Ext(device), Some(Source::Ext(source))) => device.set_selection(Some(source)),
+            (Self::Ext(device), _) => device.set_selection(None),
+            (Self::Wlr(device), Some(Source::Wlr(source))) => device.set_selection(Some(source)),
+            (Self::Wlr(device), _) => device.set_selection(None),
+            (Self::Core(device), Some(Source::Core(source))) => {
+                device.set_selection(Some(source), serial)
+            }
+            (Self::Core(device), _) => device.set_selection(None, serial),
+        }
+    }
+
+    /// Sets or clears the primary selection, which the core protocol has no request for.
+    fn set_primary_selection(&self, source: Option<&Source>) {
+        match (self, source) {
+            (Self::Ext(device), Some(Source::Ext(source))) => {
+                device.set_primary_selection(Some(source))
+            }
+            (Self::Ext(device), _) => device.set_primary_selection(None),
+            (Self::Wlr(device), Some(Source::Wlr(source))) => {
+                device.set_primary_selection(Some(source))
+            }
+            (Self::Wlr(device), _) => device.set_primary_selection(None),
+            (Self::Core(_), _) => {}
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Offer {
+    Ext(ExtDataControlOfferV1),
+    Wlr(ZwlrDataControlOfferV1),
+    Core(WlDataOffer),
+    Primary(ZwpPrimarySelectionOfferV1),
+}
+
+impl Offer {
+    fn mime_types(&self) -> Vec<String> {
+        fn of(offer: &impl Proxy) -> Vec<String> {
+            offer
+                .data::<MimeTypes>()
+                .map(|mime_types| {
+                    mime_types
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clone()
+                })
+                .unwrap_or_default()
+        }
+
+        match self {
+            Self::Ext(offer) => of(offer),
+            Self::Wlr(offer) => of(offer),
+            Self::Core(offer) => of(offer),
+            Self::Primary(offer) => of(offer),
+        }
+    }
+
+    fn receive(&self, mime_type: String, writer: &UnixStream) {
+        match self {
+            Self::Ext(offer) => offer.receive(mime_type, writer.as_fd()),
+            Self::Wlr(offer) => offer.receive(mime_type, writer.as_fd()),
+            Self::Core(offer) => offer.receive(mime_type, writer.as_fd()),
+            Self::Primary(offer) => offer.receive(mime_type, writer.as_fd()),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Self::Ext(offer) => offer.destroy(),
+            Self::Wlr(offer) => offer.destroy(),
+            Self::Core(offer) => offer.destroy(),
+            Self::Primary(offer) => offer.destroy(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Source {
+    Ext(ExtDataControlSourceV1),
+    Wlr(ZwlrDataControlSourceV1),
+    Core(WlDataSource),
+    Primary(ZwpPrimarySelectionSourceV1),
+}
+
+impl Source {
+    fn offer(&self, mime_type: String) {
+        match self {
+            Self::Ext(source) => source.offer(mime_type),
+            Self::Wlr(source) => source.offer(mime_type),
+            Self::Core(source) => source.offer(mime_type),
+            Self::Primary(source) => source.offer(mime_type),
+        }
+    }
+
+    fn contents(&self) -> Option<&Contents> {
+        match self {
+            Self::Ext(source) => source.data(),
+            Self::Wlr(source) => source.data(),
+            Self::Core(source) => source.data(),
+            Self::Primary(source) => source.data(),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            Self::Ext(source) => source.destroy(),
+            Self::Wlr(source) => source.destroy(),
+            Self::Core(source) => source.destroy(),
+            Self::Primary(source) => source.destroy(),
+        }
+    }
+}
+
+/// Serves the selections this process sets and tracks the selection of the seat.
+///
+/// A source has to send its data for as long as it is the selection, so a single connection
+/// per process dispatches its events on a background thread, independently of the
+/// [`WaylandCC`] instances that set it.
+struct Server {
+    conn: Connection,
+    qh: QueueHandle<Dispatcher>,
+    manager: Manager,
+    device: Device,
+    /// How the primary selection is accessed, if the compositor supports it.
+    primary: Option<Primary>,
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<ServerState>,
+    /// Signalled whenever the selection changes or a sync completes.
+    changed: Condvar,
+}
+
+impl Shared {
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Default)]
+struct ServerState {
+    clipboard: SelectionState,
+    primary: SelectionState,
+    /// The serial of the latest input event, which compositors check when the selection is set
+    /// through the core protocol.
+    serial: u32,
+    /// The number of syncs requested and completed.
+    syncs: (u64, u64),
+}
+
+impl ServerState {
+    fn selection(&self, selection: Selection) -> &SelectionState {
+        match selection {
+            Selection::Clipboard => &self.clipboard,
+            Selection::Primary => &self.primary,
+        }
+    }
+
+    fn selection_mut(&mut self, selection: Selection) -> &mut SelectionState {
+        match selection {
+            Selection::Clipboard => &mut self.clipboard,
+            Selection::Primary => &mut self.primary,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SelectionState {
+    /// The offer of the current selection, if the compositor has shown it to this client.
+    offer: Option<Offer>,
+    /// The source of the selection this process set, until the compositor cancels it.
+    source: Option<Source>,
+    /// How often the selection changed as far as this process can see.
+    change_count: u64,
+}
+
+impl SelectionState {
+    /// Forgets the offer of the selection this process replaces, as the core protocols only
+    /// report the new selection while this client has keyboard focus.
+    fn drop_offer(&mut self) {
+        if let Some(offer) = self.offer.take() {
+            offer.destroy();
+        }
+    }
+}
+
+/// Handles the events of the connection on the dispatch thread.
+struct Dispatcher {
+    shared: Arc<Shared>,
+    keyboard: Option<WlKeyboard>,
+    /// Whether the data control device reported a primary selection, which it only does if the
+    /// compositor supports it.
+    primary_announced: bool,
+}
+
+impl Dispatcher {
+    fn selection(&mut self, selection: Selection, offer: Option<Offer>) {
+        let mut state = self.shared.state();
+        let state = state.selection_mut(selection);
+
+        state.drop_offer();
+        state.offer = offer;
+
+        state.change_count += 1;
+        self.shared.changed.notify_all();
+    }
+
+    fn send(contents: &Contents, mime_type: &str, fd: OwnedFd) {
+        let Some((_, data)) = contents.iter().find(|(mime, _)| mime == mime_type) else {
+            return;
+        };
+        let data = data.clone();
+
+        // A slow reader must not hold up the events of the connection
+        thread::spawn(move || {
+            let _ = File::from(fd).write_all(&data);
+        });
+    }
+
+    fn cancelled(&mut self, source: Source) {
+        let mut state = self.shared.state();
+        for selection in [Selection::Clipboard, Selection::Primary] {
+            let state = state.selection_mut(selection);
+            if state.source.as_ref() == Some(&source) {
+                state.source = None;
+            }
+        }
+        source.destroy();
+    }
+}
+
+impl Server {
+    /// Returns the server of the current process, starting it if needed.
+    fn get() -> Result<Arc<Self>, ClipboardError> {
+        let mut server = SERVER.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(server) = &*server {
+            return Ok(server.clone());
+        }
+
+        let started = Self::start()?;
+        *server = Some(started.clone());
+        Ok(started)
+    }
+
+    /// Starts a server on a connection of its own, which is dispatched for the rest of the
+    /// process.
+    fn start() -> Result<Arc<Self>, ClipboardError> {
+        let conn = Connection::connect_to_env()?;
+        let (globals, mut queue) = registry_queue_init::<Dispatcher>(&conn)?;
+        let qh = queue.handle();
+
+        let seat: WlSeat = globals.bind(&qh, 1..=5, ())?;
+        let manager = Manager::bind(&globals, &qh)?;
+        let device = manager.get_data_device(&seat, &qh);
+
+        let shared = Arc::new(Shared::default());
+        let mut dispatcher = Dispatcher {
+            shared: shared.clone(),
+            keyboard: None,
+            primary_announced: false,
+        };
+
+        // Receives the current selections, if the compositor shows them to this client
+        queue.roundtrip(&mut dispatcher)?;
+
+        let primary = if dispatcher.primary_announced {
+            Some(Primary::DataControl)
+        } else if let Ok(manager) =
+            globals.bind::<ZwpPrimarySelectionDeviceManagerV1, _, _>(&qh, 1..=1, ())
+        {
+            let device = manager.get_device(&seat, &qh, ());
+            queue.roundtrip(&mut dispatcher)?;
+            Some(Primary::Zwp(manager, device))
+        } else {
+            None
+        };
+
+        thread::Builder::new()
+            .name("libclipboard-wayland".to_owned())
+            .spawn(move || {
+                while queue.blocking_dispatch(&mut dispatcher).is_ok() {}
+
+                // The connection is gone, so the next clipboard starts a new server
+                let mut server = SERVER.lock().unwrap_or_else(PoisonError::into_inner);
+                if server
+                    .as_ref()
+                    .is_some_and(|server| Arc::ptr_eq(&server.shared, &dispatcher.shared))
+                {
+                    *server = None;
+                }
+            })
+            .map_err(|error| ClipboardError::BackendUnavailable(error.to_string()))?;
+
+        Ok(Arc::new(Self {
+            conn,
+            qh,
+            manager,
+            device,
+            primary,
+            shared,
+        }))
+    }
+
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        self.shared.state()
+    }
+
+    /// Waits until the compositor has handled every request sent so far, and this process every
+    /// event the compositor sent before.
+    fn sync(&self) -> Result<(), ClipboardError> {
+        let sync = {
+            let mut state = self.state();
+            state.syncs.0 += 1;
+            state.syncs.0
+        };
+
+        self.conn.display().sync(&self.qh, sync);
+        self.conn.flush()?;
+
+        let (state, wait) = self
+            .shared
+            .changed
+            .wait_timeout_while(self.state(), READ_TIMEOUT, |state| state.syncs.1 < sync)
+            .unwrap_or_else(PoisonError::into_inner);
+        drop(state);
+
+        if wait.timed_out() {
+            return Err(ClipboardError::Timeout);
+        }
+
+        Ok(())
+    }
+
+    fn supports(&self, selection: Selection) -> bool {
+        selection == Selection::Clipboard || self.primary.is_some()
+    }
+
+    /// Returns whether the selection is only accessible with keyboard focus, which this
+    /// connection never has.
+    fn needs_focus(&self, selection: Selection) -> bool {
+        match selection {
+            Selection::Clipboard => matches!(self.manager, Manager::Core(_)),
+            Selection::Primary => matches!(self.primary, Some(Primary::Zwp(..))),
+        }
+    }
+
+    fn create_source(&self, selection: Selection, contents: Contents) -> Source {
+        match (selection, &self.primary) {
+            (Selection::Primary, Some(Primary::Zwp(manager, _))) => {
+                Source::Primary(manager.create_source(&self.qh, contents))
+            }
+            _ => self.manager.create_data_source(&self.qh, contents),
+        }
+    }
+
+    fn set_selection(&self, selection: Selection, source: Option<&Source>, serial: u32) {
+        match (selection, &self.primary) {
+            (Selection::Clipboard, _) => self.device.set_selection(source, serial),
+            (Selection::Primary, Some(Primary::Zwp(_, device))) => match source {
+                Some(Source::Primary(source)) => device.set_selection(Some(source), serial),
+                _ => device.set_selection(None, serial),
+            },
+            (Selection::Primary, _) => self.device.set_primary_selection(source),
+        }
+    }
+
+    fn set(&self, selection: Selection, contents: Contents) -> Result<(), ClipboardError> {
+        let mime_types: Vec<String> = contents.iter().map(|(mime, _)| mime.clone()).collect();
+        let source = self.create_source(selection, contents);
+
+        for mime_type in mime_types {
+            source.offer(mime_type);
+        }
+
+        let (change_count, serial) = {
+            let mut state = self.state();
+            let serial = state.serial;
+            let state = state.selection_mut(selection);
+            state.source = Some(source.clone());
+            state.drop_offer();
+            (state.change_count, serial)
+        };
+
+        self.set_selection(selection, Some(&source), serial);
+        self.count_own_change(selection, change_count)
+    }
+
+    fn clear(&self, selection: Selection) -> Result<bool, ClipboardError> {
+        let (change_count, serial, owned) = {
+            let mut state = self.state();
+            let serial = state.serial;
+            let state = state.selection_mut(selection);
+            let owned = state.source.take().is_some();
+            state.drop_offer();
+            (state.change_count, serial, owned)
+        };
+
+        self.set_selection(selection, None, serial);
+        self.count_own_change(selection, change_count)?;
+
+        Ok(owned)
+    }
+
+    /// Counts a change this process made to the selection, which had `change_count` before,
+    /// unless the compositor already reported it.
+    ///
+    /// Without data control, compositors report the change to the client with keyboard focus
+    /// and ignore the request of any other client, so an unreported change failed.
+    fn count_own_change(
+        &self,
+        selection: Selection,
+        change_count: u64,
+    ) -> Result<(), ClipboardError> {
+        self.sync()?;
+
+        let needs_focus = self.needs_focus(selection);
+        let mut state = self.state();
+        let state = state.selection_mut(selection);
+
+        if state.change_count != change_count {
+            return Ok(());
+        }
+
+        if needs_focus {
+            if let Some(source) = state.source.take() {
+                source.destroy();
+            }
+
+            return Err(ClipboardError::BackendUnavailable(
+                "the compositor ignored the change, as this client has no keyboard focus"
+                    .to_owned(),
+            ));
+        }
+
+        state.change_count += 1;
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    fn change_count(&self, selection: Selection) -> u64 {
+        self.state().selection(selection).change_count
+    }
+
+    /// Blocks until the change count differs from `change_count` or `timeout` elapses.
+    fn wait_for_change(&self, selection: Selection, change_count: u64, timeout: Duration) {
+        let _ = self
+            .shared
+            .changed
+            .wait_timeout_while(self.state(), timeout, |state| {
+                state.selection(selection).change_count == change_count
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+
+    /// Returns the MIME types the selection is offered as.
+    fn mime_types(&self, selection: Selection) -> Vec<String> {
+        let state = self.state();
+        let state = state.selection(selection);
+
+        if let Some(source) = &state.source {
+            return source
+                .contents()
+                .map(|contents| contents.iter().map(|(mime, _)| mime.clone()).collect())
+                .unwrap_or_default();
+        }
+
+        state
+            .offer
+            .as_ref()
+            .map(Offer::mime_types)
+            .unwrap_or_default()
+    }
+
+    /// Reads the selection as `mime_type`, returning `None` if there is no selection.
+    fn receive(
+        &self,
+        selection: Selection,
+        mime_type: &str,
+    ) -> Result<Option<Vec<u8>>, ClipboardError> {
+        let offer = {
+            let state = self.state();
+            let state = state.selection(selection);
+
+            // Data this process set is read without a round trip through the compositor
+            if let Some(source) = &state.source {
+                return Ok(source.contents().and_then(|contents| {
+                    contents
+                        .iter()
+                        .find(|(mime, _)| mime == mime_type)
+                        .map(|(_, data)| data.clone())
+                }));
+            }
+
+            match &state.offer {
+                Some(offer) => offer.clone(),
+                None => return Ok(None),
+            }
+        };
+
+        let (mut reader, writer) = UnixStream::pair().map_err(io_error)?;
+        offer.receive(mime_type.to_owned(), &writer);
+        drop(writer);
+        self.conn.flush()?;
+
+        reader
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(io_error)?;
+
+        let mut data = Vec::new();
+        match reader.read_to_end(&mut data) {
+            Ok(_) => Ok(Some(data)),
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Err(ClipboardError::Timeout)
+            }
+            Err(error) => Err(io_error(error)),
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            // Keyboard events carry the serials needed to set the selection
+            if capabilities.contains(wl_seat::Capability::Keyboard) && dispatcher.keyboard.is_none()
+            {
+                dispatcher.keyboard = Some(seat.get_keyboard(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        _: &WlKeyboard,
+        event: wl_keyboard::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_keyboard::Event::Enter { serial, .. }
+            | wl_keyboard::Event::Key { serial, .. }
+            | wl_keyboard::Event::Modifiers { serial, .. } => {
+                dispatcher.shared.state().serial = serial;
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(Dispatcher: WlDataDeviceManager);
+delegate_noop!(Dispatcher: ExtDataControlManagerV1);
+delegate_noop!(Dispatcher: ZwlrDataControlManagerV1);
+delegate_noop!(Dispatcher: ZwpPrimarySelectionDeviceManagerV1);
+
+impl Dispatch<WlDataDevice, ()> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        _: &WlDataDevice,
+        event: wl_data_device::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_data_device::Event::Selection { id } = event {
+            dispatcher.selection(Selection::Clipboard, id.map(Offer::Core));
+        }
+    }
+
+    event_created_child!(Dispatcher, WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (WlDataOffer, MimeTypes::default()),
+    ]);
+}
+
+impl Dispatch<WlDataOffer, MimeTypes> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlDataOffer,
+        event: wl_data_offer::Event,
+        mime_types: &MimeTypes,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_data_offer::Event::Offer { mime_type } = event {
+            mime_types
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<WlDataSource, Contents> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        source: &WlDataSource,
+        event: wl_data_source::Event,
+        contents: &Contents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_source::Event::Send { mime_type, fd } => Self::send(contents, &mime_type, fd),
+            wl_data_source::Event::Cancelled => dispatcher.cancelled(Source::Core(source.clone())),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionDeviceV1, ()> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        _: &ZwpPrimarySelectionDeviceV1,
+        event: zwp_primary_selection_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_primary_selection_device_v1::Event::Selection { id } = event {
+            dispatcher.selection(Selection::Primary, id.map(Offer::Primary));
+        }
+    }
+
+    event_created_child!(Dispatcher, ZwpPrimarySelectionDeviceV1, [
+        zwp_primary_selection_device_v1::EVT_DATA_OFFER_OPCODE => (ZwpPrimarySelectionOfferV1, MimeTypes::default()),
+    ]);
+}
+
+impl Dispatch<ZwpPrimarySelectionOfferV1, MimeTypes> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &ZwpPrimarySelectionOfferV1,
+        event: zwp_primary_selection_offer_v1::Event,
+        mime_types: &MimeTypes,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwp_primary_selection_offer_v1::Event::Offer { mime_type } = event {
+            mime_types
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(mime_type);
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionSourceV1, Contents> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        source: &ZwpPrimarySelectionSourceV1,
+        event: zwp_primary_selection_source_v1::Event,
+        contents: &Contents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_primary_selection_source_v1::Event::Send { mime_type, fd } => {
+                Self::send(contents, &mime_type, fd)
+            }
+            zwp_primary_selection_source_v1::Event::Cancelled => {
+                dispatcher.cancelled(Source::Primary(source.clone()))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Handles the events of a data control protocol, which `ext` adopted from `wlr` unchanged.
+macro_rules! dispatch_data_control {
+    ($variant:ident, $device:ident, $device_mod:ident, $offer:ident, $offer_mod:ident, $source:ident, $source_mod:ident) => {
+        impl Dispatch<$device, ()> for Dispatcher {
+            fn event(
+                dispatcher: &mut Self,
+                _: &$device,
+                event: $device_mod::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $device_mod::Event::Selection { id } => {
+                        dispatcher.selection(Selection::Clipboard, id.map(Offer::$variant))
+                    }
+                    $device_mod::Event::PrimarySelection { id } => {
+                        dispatcher.primary_announced = true;
+                        dispatcher.selection(Selection::Primary, id.map(Offer::$variant))
+                    }
+                    _ => {}
+                }
+            }
+
+            event_created_child!(Dispatcher, $device, [
+                $device_mod::EVT_DATA_OFFER_OPCODE => ($offer, MimeTypes::default()),
+            ]);
+        }
+
+        impl Dispatch<$offer, MimeTypes> for Dispatcher {
+            fn event(
+                _: &mut Self,
+                _: &$offer,
+                event: $offer_mod::Event,
+                mime_types: &MimeTypes,
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                if let $offer_mod::Event::Offer { mime_type } = event {
+                    mime_types
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(mime_type);
+                }
+            }
+        }
+
+        impl Dispatch<$source, Contents> for Dispatcher {
+            fn event(
+                dispatcher: &mut Self,
+                source: &$source,
+                event: $source_mod::Event,
+                contents: &Contents,
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $source_mod::Event::Send { mime_type, fd } => {
+                        Self::send(contents, &mime_type, fd)
+                    }
+                    $source_mod::Event::Cancelled => {
+                        dispatcher.cancelled(Source::$variant(source.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+dispatch_data_control!(
+    Ext,
+    ExtDataControlDeviceV1,
+    ext_data_control_device_v1,
+    ExtDataControlOfferV1,
+    ext_data_control_offer_v1,
+    ExtDataControlSourceV1,
+    ext_data_control_source_v1
+);
+dispatch_data_control!(
+    Wlr,
+    ZwlrDataControlDeviceV1,
+    zwlr_data_control_device_v1,
+    ZwlrDataControlOfferV1,
+    zwlr_data_control_offer_v1,
+    ZwlrDataControlSourceV1,
+    zwlr_data_control_source_v1
+);
+
+impl Dispatch<WlCallback, u64> for Dispatcher {
+    fn event(
+        dispatcher: &mut Self,
+        _: &WlCallback,
+        event: wl_callback::Event,
+        sync: &u64,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            let mut state = dispatcher.shared.state();
+            state.syncs.1 = state.syncs.1.max(*sync);
+            dispatcher.shared.changed.notify_all();
+        }
+    }
+}
+
+/// The clipboard of a Wayland seat.
+///
+/// Compositors that implement `ext-data-control-v1` or `wlr-data-control-unstable-v1` give
+/// this connection, which has no surfaces, full access to the selection. Otherwise it falls
+/// back to the core `wl_data_device` protocol, with which compositors only show the selection
+/// to the client with keyboard focus and only let it set the selection in response to input,
+/// so writing fails and the selections of other clients stay hidden; see
+/// [`has_background_access`](Self::has_background_access).
+pub struct WaylandCC {
+    kind: ClipboardKind,
+    selection: Selection,
+    server: Arc<Server>,
+    /// The change count when `has_clipboard_changed` was last called.
+    last_change_count: Cell<u64>,
+}
+
+impl fmt::Debug for WaylandCC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaylandCC")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WaylandCC {
+    pub fn new() -> Result<Self, ClipboardError> {
+        Self::with_kind(ClipboardKind::Clipboard)
+    }
+
+    /// Opens the clipboard or the primary selection of the first seat, if the compositor
+    /// supports it. Wayland has no other clipboards.
+    pub fn with_kind(kind: ClipboardKind) -> Result<Self, ClipboardError> {
+        Self::open(kind, Server::get)
+    }
+
+    /// Returns whether the compositor gives access to the selection without keyboard focus,
+    /// through `ext-data-control-v1` or `wlr-data-control-unstable-v1`.
+    ///
+    /// Otherwise this client, which has no surfaces, can neither see what other clients copy
+    /// nor set the selection.
+    pub fn has_background_access(&self) -> bool {
+        !self.server.needs_focus(self.selection)
+    }
+
+    fn open(
+        kind: ClipboardKind,
+        server: impl FnOnce() -> Result<Arc<Server>, ClipboardError>,
+    ) -> Result<Self, ClipboardError> {
+        let selection = match kind {
+            ClipboardKind::Clipboard => Selection::Clipboard,
+            ClipboardKind::Primary => Selection::Primary,
+            _ => return Err(ClipboardError::UnsupportedKind(kind)),
+        };
+
+        let server = server()?;
+        if !server.supports(selection) {
+            return Err(ClipboardError::UnsupportedKind(kind));
+        }
+
+        Ok(Self {
+            kind,
+            selection,
+            last_change_count: Cell::new(server.change_count(selection)),
+            server,
+        })
+    }
+
+    pub fn has_clipboard_changed(&self) -> bool {
+        let change_count = self.server.change_count(self.selection);
+        change_count != self.last_change_count.replace(change_count)
+    }
+
+    /// Blocks until [`has_clipboard_changed`](Self::has_clipboard_changed) would return `true`
+    /// or `timeout` elapses, returning whether the clipboard changed.
+    pub fn wait_for_clipboard_change(&self, timeout: Duration) -> bool {
+        self.server
+            .wait_for_change(self.selection, self.last_change_count.get(), timeout);
+        self.has_clipboard_changed()
+    }
+
+    /// Reads the first of `mime_types` that holds `kind`, trying them in the order of the
+    /// format registry.
+    fn read_format(
+        &self,
+        mime_types: &[String],
+        kind: &FormatKind,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let mut candidates: Vec<_> = mime_types
+            .iter()
+            .filter(|mime_type| mime::holds(mime_type, kind))
+            .collect();
+        candidates.sort_by_key(|mime_type| mime::preference(kind, mime_type));
+
+        for mime_type in candidates {
+            let Some(data) = self.server.receive(self.selection, mime_type)? else {
+                continue;
+            };
+
+            // A URI list holds either URLs or files
+            match mime::decode_item(mime_type, data) {
+                Some(item) if item.kind() == *kind => return Ok(Some(item)),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn kinds(mime_types: &[String]) -> Vec<FormatKind> {
+        mime::offered_kinds(mime_types.iter().map(String::as_str))
+    }
+}
+
+impl ClipboardBackend for WaylandCC {
+    fn kind(&self) -> ClipboardKind {
+        self.kind.clone()
+    }
+
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let mime_types = self.server.mime_types(self.selection);
+
+        for kind in Self::kinds(&mime_types) {
+            if let Some(item) = self.read_format(&mime_types, &kind)? {
+                return Ok(Some(item));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        let mime_types = self.server.mime_types(self.selection);
+        let mut items: Vec<ClipboardItem> = Vec::new();
+
+        for kind in Self::kinds(&mime_types) {
+            if let Some(item) = self.read_format(&mime_types, &kind)? {
+                if !items.iter().any(|existing| existing.kind() == item.kind()) {
+                    items.push(item);
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.read_format(&self.server.mime_types(self.selection), kind)
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        let mut contents = Contents::new();
+
+        for item in items {
+            for mime_type in mime::item_targets(item) {
+                if !contents.iter().any(|(existing, _)| *existing == mime_type) {
+                    let data = mime::encode_item(item, &mime_type)?;
+                    contents.push((mime_type, data));
+                }
+            }
+        }
+
+        if options.concealed || options.exclude_from_history {
+            contents.push((KDE_PASSWORD_MANAGER_HINT.to_owned(), KDE_SECRET.to_vec()));
+        }
+
+        self.server.set(self.selection, contents)
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        let secret = self
+            .server
+            .mime_types(self.selection)
+            .iter()
+            .any(|mime_type| mime_type == KDE_PASSWORD_MANAGER_HINT)
+            && self
+                .server
+                .receive(self.selection, KDE_PASSWORD_MANAGER_HINT)?
+                .is_some_and(|data| data == KDE_SECRET);
+
+        Ok(SetOptions {
+            concealed: secret,
+            exclude_from_history: secret,
+            ..SetOptions::default()
+        })
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.server.clear(self.selection)
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        Ok(self
+            .server
+            .mime_types(self.selection)
+            .into_iter()
+            .map(|mime_type| FormatInfo {
+                kind: mime::target_kind(&mime_type),
+                native: NativeFormat::MimeType(mime_type),
+                size: None,
+            })
+            .collect())
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        let mut entries = Vec::new();
+
+        for mime_type in self.server.mime_types(self.selection) {
+            if let Some(data) = self.server.receive(self.selection, &mime_type)? {
+                entries.push(SnapshotEntry {
+                    native: NativeFormat::MimeType(mime_type),
+                    data,
+                });
+            }
+        }
+
+        Ok(ClipboardSnapshot { entries })
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        let mut contents = Contents::new();
+
+        for entry in &snapshot.entries {
+            match &entry.native {
+                NativeFormat::MimeType(mime_type) => {
+                    contents.push((mime_type.clone(), entry.data.clone()))
+                }
+                native => return Err(ClipboardError::UnsupportedFormat(format!("{native:?}"))),
+            }
+        }
+
+        self.server.set(self.selection, contents)
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        Ok(self.server.change_count(self.selection))
+    }
+
+    fn has_changed(&self) -> bool {
+        self.has_clipboard_changed()
+    }
+
+    fn wait_for_change(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        Ok(self.wait_for_clipboard_change(timeout))
+    }
+
+    fn factory(&self) -> BackendFactory {
+        let kind = self.kind.clone();
+
+        Arc::new(move || Ok(Box::new(WaylandCC::with_kind(kind.clone())?)))
+    }
+}
+
+fn io_error(error: std::io::Error) -> ClipboardError {
+    ClipboardError::Os {
+        code: error.raw_os_error().unwrap_or_default(),
+        message: error.to_string(),
+    }
+}
+
+impl From<ConnectError> for ClipboardError {
+    fn from(error: ConnectError) -> Self {
+        Self::BackendUnavailable(error.to_string())
+    }
+}
+
+impl From<GlobalError> for ClipboardError {
+    fn from(error: GlobalError) -> Self {
+        Self::BackendUnavailable(error.to_string())
+    }
+}
+
+impl From<BindError> for ClipboardError {
+    fn from(error: BindError) -> Self {
+        Self::BackendUnavailable(error.to_string())
+    }
+}
+
+impl From<WaylandError> for ClipboardError {
+    fn from(error: WaylandError) -> Self {
+        match error {
+            WaylandError::Io(error) => io_error(error),
+            WaylandError::Protocol(error) => Self::BackendUnavailable(error.to_string()),
+        }
+    }
+}
+
+impl From<DispatchError> for ClipboardError {
+    fn from(error: DispatchError) -> Self {
+        match error {
+            DispatchError::Backend(error) => error.into(),
+            DispatchError::BadMessage { .. } => Self::BackendUnavailable(error.to_string()),
+        }
+    }
+}
@@ -1,22 +1,33 @@
 use std::{
-    ffi::{c_void, CStr},
+    cell::Cell,
+    ffi::c_void,
+    io::Cursor,
     ptr,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 
 use uuid::Uuid;
 use windows::{
-    core::Error,
+    core::{Error, HSTRING, PCSTR},
     Win32::{
-        Foundation::{GlobalFree, HGLOBAL, HWND, LPARAM, LRESULT, POINT, WPARAM},
-        Globalization::{MultiByteToWideChar, WideCharToMultiByte, CP_UTF8},
+        Foundation::{
+            GetLastError, GlobalFree, ERROR_CLASS_ALREADY_EXISTS, HANDLE, HGLOBAL, HWND, LPARAM,
+            LRESULT, WPARAM,
+        },
+        Globalization::{
+            MultiByteToWideChar, WideCharToMultiByte, CP_ACP, MULTI_BYTE_TO_WIDE_CHAR_FLAGS,
+        },
         System::{
             DataExchange::{
                 AddClipboardFormatListener, CloseClipboard, CountClipboardFormats, EmptyClipboard,
-                EnumClipboardFormats, GetClipboardData, OpenClipboard,
+                EnumClipboardFormats, GetClipboardData, GetClipboardFormatNameW, GetClipboardOwner,
+                GetClipboardSequenceNumber, OpenClipboard, RegisterClipboardFormatW,
                 RemoveClipboardFormatListener, SetClipboardData,
             },
             LibraryLoader::GetModuleHandleA,
-            Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GHND},
+            Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE},
             Ole::{
                 CF_BITMAP, CF_DIB, CF_DIBV5, CF_DIF, CF_DSPBITMAP, CF_DSPENHMETAFILE,
                 CF_DSPMETAFILEPICT, CF_DSPTEXT, CF_ENHMETAFILE, CF_GDIOBJFIRST, CF_GDIOBJLAST,
@@ -26,14 +37,41 @@ use windows::{
             },
         },
         UI::WindowsAndMessaging::{
-            CreateWindowExA, DefWindowProcA, DispatchMessageA, GetMessageA, RegisterClassExA,
-            CS_DBLCLKS, CS_HREDRAW, CS_VREDRAW, HWND_MESSAGE, MSG, WINDOW_EX_STYLE,
-            WM_CLIPBOARDUPDATE, WNDCLASSEXA, WS_OVERLAPPEDWINDOW,
+            CreateWindowExA, DefWindowProcA, DestroyWindow, DispatchMessageA,
+            MsgWaitForMultipleObjects, PeekMessageA, RegisterClassExA, CS_DBLCLKS, CS_HREDRAW,
+            CS_VREDRAW, HWND_MESSAGE, MSG, PM_REMOVE, QS_ALLINPUT, WINDOW_EX_STYLE, WNDCLASSEXA,
+            WS_OVERLAPPEDWINDOW,
         },
     },
 };
 
-use crate::models::ClipboardItem;
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    error::ClipboardError,
+    formats::{CustomFormat, FormatInfo, FormatKind, NativeFormat, WindowsFormat},
+    image_data::{ImageData, ImageFormat},
+    models::ClipboardItem,
+    options::SetOptions,
+    snapshot::{ClipboardSnapshot, SnapshotEntry},
+};
+
+/// Number of times opening the clipboard is attempted before giving up with
+/// [`ClipboardError::ClipboardBusy`].
+const OPEN_ATTEMPTS: usize = 10;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Longest time `wait_for_clipboard_change` waits for a message before checking the clipboard
+/// sequence number again.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Registered formats that tell clipboard monitors and the clipboard history how to treat the
+/// other formats.
+const EXCLUDE_FROM_MONITOR_FORMAT: &str = "ExcludeClipboardContentFromMonitorProcessing";
+const INCLUDE_IN_HISTORY_FORMAT: &str = "CanIncludeInClipboardHistory";
+const UPLOAD_TO_CLOUD_FORMAT: &str = "CanUploadToCloudClipboard";
+
+/// Size of the `DROPFILES` header that precedes the file list in `CF_HDROP` data.
+const DROPFILES_SIZE: usize = 20;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u16)]
@@ -151,17 +189,17 @@ impl ClipboardFormat {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct WindowsCC {
     msg_only_hwnd: HWND,
+    /// The clipboard sequence number when `has_clipboard_changed` was last called.
+    last_sequence_number: Cell<u32>,
 }
 
-pub static mut CLIPBOARD_CHANGED: bool = false;
-
 impl WindowsCC {
-    pub fn new() -> Result<Self, &'static str> {
+    pub fn new() -> Result<Self, ClipboardError> {
         unsafe {
-            let sz_class_name = "#32769";
+            let sz_class_name = "libclipboard\0";
 
             let wc = WNDCLASSEXA {
                 cbSize: std::mem::size_of::<WNDCLASSEXA>() as u32,
@@ -169,26 +207,27 @@ impl WindowsCC {
                 lpfnWndProc: Some(window_proc),
                 cbClsExtra: 0,
                 cbWndExtra: 0,
-                hInstance: GetModuleHandleA(None).unwrap().into(),
+                hInstance: GetModuleHandleA(None)?.into(),
                 hIcon: windows::Win32::UI::WindowsAndMessaging::HICON(0),
                 hCursor: windows::Win32::UI::WindowsAndMessaging::HCURSOR(0),
                 hbrBackground: windows::Win32::Graphics::Gdi::HBRUSH(0),
-                lpszMenuName: windows::core::PCSTR(ptr::null_mut()),
-                lpszClassName: windows::core::PCSTR(sz_class_name.as_ptr()),
+                lpszMenuName: PCSTR(ptr::null_mut()),
+                lpszClassName: PCSTR(sz_class_name.as_ptr()),
                 hIconSm: windows::Win32::UI::WindowsAndMessaging::HICON(0),
             };
 
-            if RegisterClassExA(&wc) == 0 {
-                return Err("Failed to create Window class");
+            // The class is shared by every instance, so it only has to be registered once
+            if RegisterClassExA(&wc) == 0 && GetLastError() != ERROR_CLASS_ALREADY_EXISTS {
+                return Err(Error::from_win32().into());
             }
 
-            let window_name = Uuid::new_v4().to_string();
+            let window_name = format!("{}\0", Uuid::new_v4());
 
             // Creates a new window handle
             let msg_only_hwnd = CreateWindowExA(
                 WINDOW_EX_STYLE::default(),
                 wc.lpszClassName,
-                windows::core::PCSTR(window_name.as_ptr()),
+                PCSTR(window_name.as_ptr()),
                 WS_OVERLAPPEDWINDOW,
                 0,
                 0,
@@ -201,264 +240,690 @@ impl WindowsCC {
             );
 
             if msg_only_hwnd.0 == 0 {
-                return Err("Window handle could not be created");
+                return Err(Error::from_win32().into());
             }
 
             // Registers the window to receive clipboard updates
             let _ = AddClipboardFormatListener(msg_only_hwnd);
 
-            Ok(Self { msg_only_hwnd })
+            Ok(Self {
+                msg_only_hwnd,
+                last_sequence_number: Cell::new(GetClipboardSequenceNumber()),
+            })
         }
     }
 
     pub fn clipboard_format_as_clipboard_item(
         &self,
         format: ClipboardFormat,
-    ) -> Option<ClipboardItem> {
-        Some(match format {
-            ClipboardFormat::TEXT => ClipboardItem::Text(self.get_text_from_clipboard()?),
-            ClipboardFormat::UNICODETEXT => {
-                ClipboardItem::UnicodeText(self.get_unicode_text_from_clipboard()?)
-            }
-            _ => return None,
-        })
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let clipboard = self.open()?;
+
+        Self::read_item(&clipboard, format as u32)
+    }
+
+    pub fn get_clipboard_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        Self::read_items(&self.open()?)
     }
 
-    pub fn get_clipboard_items(&self) -> Option<Vec<ClipboardItem>> {
+    /// Reads the items and the markers on the clipboard while it is open, so that both describe
+    /// the same contents.
+    pub fn get_clipboard_items_with_options(
+        &self,
+    ) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        let clipboard = self.open()?;
+
+        Ok((
+            Self::read_items(&clipboard)?,
+            Self::read_options(&clipboard)?,
+        ))
+    }
+
+    pub fn get_clipboard_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        Self::read_first_item(&self.open()?)
+    }
+
+    /// Like [`get_clipboard_items_with_options`](Self::get_clipboard_items_with_options), but
+    /// only reads the first item.
+    pub fn get_clipboard_item_with_options(
+        &self,
+    ) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        let clipboard = self.open()?;
+
+        match Self::read_first_item(&clipboard)? {
+            Some(item) => Ok(Some((item, Self::read_options(&clipboard)?))),
+            None => Ok(None),
+        }
+    }
+
+    fn read_items(clipboard: &OpenClipboardGuard) -> Result<Vec<ClipboardItem>, ClipboardError> {
         let mut result = vec![];
-        let no_formats = self.get_number_of_formats();
-        let mut next_format = 0;
 
-        for _ in 0..no_formats {
-            next_format = self.get_next_format(next_format);
+        for format in clipboard.formats() {
+            match Self::read_item(clipboard, format) {
+                Ok(Some(item)) => result.push(item),
+                // Formats that can't be represented are skipped rather than failing the whole read
+                Ok(None) | Err(ClipboardError::ConversionFailed(_)) => continue,
+                Err(error) => return Err(error),
+            }
+        }
 
-            result.push(match self.get_clipboard_item_with_format(next_format) {
-                Some(item) => item,
-                None => continue,
-            });
+        Ok(result)
+    }
+
+    fn read_first_item(
+        clipboard: &OpenClipboardGuard,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        for format in clipboard.formats() {
+            match Self::read_item(clipboard, format) {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) | Err(ClipboardError::ConversionFailed(_)) => continue,
+                Err(error) => return Err(error),
+            }
         }
 
-        Some(result)
+        Ok(None)
+    }
+
+    pub fn get_clipboard_format(
+        &self,
+        kind: &FormatKind,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let clipboard = self.open()?;
+
+        for format in clipboard.formats() {
+            if format_kind(format, format_name(format).as_deref()) != *kind {
+                continue;
+            }
+
+            match Self::read_item(&clipboard, format) {
+                Ok(Some(item)) => return Ok(Some(item)),
+                Ok(None) | Err(ClipboardError::ConversionFailed(_)) => continue,
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn set_clipboard_item(&mut self, item: ClipboardItem) -> Result<(), ClipboardError> {
+        self.set_clipboard_items(&[item])
+    }
+
+    pub fn set_clipboard_items(&mut self, items: &[ClipboardItem]) -> Result<(), ClipboardError> {
+        self.set_clipboard_items_with_options(items, &SetOptions::default())
     }
 
-    pub fn get_clipboard_item(&self) -> Option<ClipboardItem> {
-        let next_available_format = self.get_next_format(0);
-        let format = ClipboardFormat::from_u16(next_available_format);
+    pub fn set_clipboard_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        let mut formats: Vec<(u32, Vec<u8>)> = Vec::with_capacity(items.len());
+
+        for item in items {
+            let (format, data) = encode_item(item)?;
+
+            // The first representation of a format wins, matching the other backends
+            if !formats.iter().any(|(existing, _)| *existing == format) {
+                formats.push((format, data));
+            }
+        }
+
+        // The markers are DWORDs; monitors only check whether the exclusion format is present
+        if options.concealed {
+            formats.push((register_format(EXCLUDE_FROM_MONITOR_FORMAT)?, vec![0; 4]));
+        }
+
+        if options.exclude_from_history {
+            formats.push((register_format(INCLUDE_IN_HISTORY_FORMAT)?, vec![0; 4]));
+            formats.push((register_format(UPLOAD_TO_CLOUD_FORMAT)?, vec![0; 4]));
+        }
+
+        let clipboard = self.open()?;
+
+        // Empties clipboard and makes the current window the owner of the clipboard
+        clipboard.empty()?;
 
-        self.clipboard_format_as_clipboard_item(format?)
+        for (format, data) in formats {
+            clipboard.set_data(format, &data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the markers written by `set_clipboard_items_with_options`. Windows has no marker
+    /// for transient data.
+    pub fn get_clipboard_options(&self) -> Result<SetOptions, ClipboardError> {
+        Self::read_options(&self.open()?)
     }
 
-    pub fn set_clipboard_item(&mut self, item: ClipboardItem) {
-        match item {
-            ClipboardItem::Text(text) => self.set_text_from_clipboard(&text).unwrap(),
-            ClipboardItem::UnicodeText(text) => {
-                self.set_unicode_text_from_clipboard(&text).unwrap()
+    fn read_options(clipboard: &OpenClipboardGuard) -> Result<SetOptions, ClipboardError> {
+        let mut options = SetOptions::default();
+
+        for format in clipboard.formats() {
+            match format_name(format).as_deref() {
+                Some(EXCLUDE_FROM_MONITOR_FORMAT) => options.concealed = true,
+                Some(INCLUDE_IN_HISTORY_FORMAT) => {
+                    // `GlobalSize` may round the allocation up, so only the DWORD is compared
+                    let data = clipboard.get_data(format)?.unwrap_or_default();
+                    options.exclude_from_history = data
+                        .first_chunk::<4>()
+                        .is_some_and(|dword| u32::from_le_bytes(*dword) == 0);
+                }
+                _ => {}
             }
-            _ => todo!(),
         }
+
+        Ok(options)
+    }
+
+    /// Empties the clipboard, returning `true` if its contents were written by this instance.
+    pub fn clear_clipboard(&mut self) -> Result<bool, ClipboardError> {
+        let clipboard = self.open()?;
+
+        // Emptying an already empty clipboard also makes us its owner
+        let owned =
+            unsafe { GetClipboardOwner() } == self.msg_only_hwnd && !clipboard.formats().is_empty();
+
+        clipboard.empty()?;
+        Ok(owned)
     }
 
     pub fn get_number_of_formats(&self) -> i32 {
         unsafe { CountClipboardFormats() }
     }
 
+    pub fn get_available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        let clipboard = self.open()?;
+
+        Ok(clipboard
+            .formats()
+            .into_iter()
+            .map(|id| {
+                let name = format_name(id);
+
+                FormatInfo {
+                    kind: format_kind(id, name.as_deref()),
+                    native: NativeFormat::Windows { id, name },
+                    // Getting the size would make the owner render delayed formats
+                    size: None,
+                }
+            })
+            .collect())
+    }
+
+    /// Captures every format on the clipboard that is stored in global memory.
+    ///
+    /// Formats backed by GDI objects (bitmaps, metafiles, palettes) and private handles can't be
+    /// copied as bytes and are left out.
+    pub fn snapshot_clipboard(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        let clipboard = self.open()?;
+        let mut entries = vec![];
+
+        for id in clipboard.formats() {
+            if !is_global_memory_format(id) {
+                continue;
+            }
+
+            if let Some(data) = clipboard.get_data(id)? {
+                entries.push(SnapshotEntry {
+                    native: NativeFormat::Windows {
+                        id,
+                        name: format_name(id),
+                    },
+                    data,
+                });
+            }
+        }
+
+        Ok(ClipboardSnapshot { entries })
+    }
+
+    pub fn restore_clipboard(
+        &mut self,
+        snapshot: &ClipboardSnapshot,
+    ) -> Result<(), ClipboardError> {
+        let formats = snapshot
+            .entries
+            .iter()
+            .map(|entry| match &entry.native {
+                // Registered formats can get a different id in another session
+                NativeFormat::Windows {
+                    name: Some(name), ..
+                } => Ok((register_format(name)?, entry.data.as_slice())),
+                NativeFormat::Windows { id, name: None } => Ok((*id, entry.data.as_slice())),
+                native => Err(ClipboardError::UnsupportedFormat(format!("{native:?}"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let clipboard = self.open()?;
+        clipboard.empty()?;
+
+        for (format, data) in formats {
+            clipboard.set_data(format, data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the clipboard sequence number changed since the last call.
     pub fn has_clipboard_changed(&self) -> bool {
-        let mut msg = MSG {
-            hwnd: windows::Win32::Foundation::HWND::default(),
-            message: 0,
-            wParam: windows::Win32::Foundation::WPARAM::default(),
-            lParam: windows::Win32::Foundation::LPARAM::default(),
-            time: 0,
-            pt: POINT { x: 0, y: 0 },
-        };
+        let sequence_number = unsafe { GetClipboardSequenceNumber() };
+        self.last_sequence_number.replace(sequence_number) != sequence_number
+    }
 
-        unsafe {
-            let res = GetMessageA(&mut msg, HWND::default(), 0, 0);
+    /// Blocks until the clipboard changes or `timeout` elapses, pumping the window's messages
+    /// in the meantime.
+    pub fn wait_for_clipboard_change(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.has_clipboard_changed() {
+                return true;
+            }
 
-            if res.as_bool() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
                 return false;
             }
-            DispatchMessageA(&msg);
 
-            match CLIPBOARD_CHANGED {
-                true => {
-                    CLIPBOARD_CHANGED = false;
-                    true
+            // `WM_CLIPBOARDUPDATE` only wakes the thread that created the window, so the wait is
+            // capped in case this is called from another thread
+            let wait = remaining.min(WAIT_POLL_INTERVAL);
+
+            unsafe {
+                MsgWaitForMultipleObjects(None, false, wait.as_millis() as u32, QS_ALLINPUT);
+
+                let mut msg = MSG::default();
+                while PeekMessageA(&mut msg, self.msg_only_hwnd, 0, 0, PM_REMOVE).as_bool() {
+                    DispatchMessageA(&msg);
                 }
-                false => false,
             }
         }
     }
 
-    fn get_next_format(&self, mut next_format: u16) -> u16 {
-        unsafe {
-            if OpenClipboard(None).is_ok() {
-                next_format = EnumClipboardFormats(next_format as u32) as u16;
-                let _ = CloseClipboard();
+    fn open(&self) -> Result<OpenClipboardGuard, ClipboardError> {
+        OpenClipboardGuard::open(self.msg_only_hwnd)
+    }
+
+    fn read_item(
+        clipboard: &OpenClipboardGuard,
+        format: u32,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let kind = format_kind(format, format_name(format).as_deref());
+
+        // Only formats stored in global memory are read; GDI handles such as `CF_BITMAP` are not
+        if kind == FormatKind::Unknown {
+            return Ok(None);
+        }
+
+        clipboard
+            .get_data(format)?
+            .map(|data| decode_item(kind, data))
+            .transpose()
+    }
+}
+
+/// Keeps the clipboard open until it is dropped.
+struct OpenClipboardGuard;
+
+impl OpenClipboardGuard {
+    fn open(owner: HWND) -> Result<Self, ClipboardError> {
+        for attempt in 0..OPEN_ATTEMPTS {
+            if unsafe { OpenClipboard(owner) }.is_ok() {
+                return Ok(Self);
             }
 
-            next_format
+            // Another process has the clipboard open; it usually only holds it briefly
+            if attempt + 1 < OPEN_ATTEMPTS {
+                thread::sleep(OPEN_RETRY_DELAY);
+            }
         }
+
+        Err(ClipboardError::ClipboardBusy)
     }
 
-    fn get_clipboard_item_with_format(&self, format: u16) -> Option<ClipboardItem> {
+    fn empty(&self) -> Result<(), ClipboardError> {
+        unsafe { EmptyClipboard()? };
+        Ok(())
+    }
+
+    fn formats(&self) -> Vec<u32> {
+        let mut formats = vec![];
+        let mut format = 0;
+
+        loop {
+            format = unsafe { EnumClipboardFormats(format) };
+            if format == 0 {
+                break;
+            }
+            formats.push(format);
+        }
+
+        formats
+    }
+
+    fn get_data(&self, format: u32) -> Result<Option<Vec<u8>>, ClipboardError> {
         unsafe {
-            let _ = OpenClipboard(None);
+            let hglb = match GetClipboardData(format) {
+                Ok(handle) => HGLOBAL(handle.0 as *mut c_void),
+                Err(_) => return Ok(None),
+            };
 
-            let format = ClipboardFormat::from_u16(format);
+            let data = GlobalLock(hglb);
+            if data.is_null() {
+                return Err(Error::from_win32().into());
+            }
 
-            let _ = CloseClipboard();
+            let bytes = std::slice::from_raw_parts(data as *const u8, GlobalSize(hglb)).to_vec();
+            let _ = GlobalUnlock(hglb);
 
-            self.clipboard_format_as_clipboard_item(format?)
+            Ok(Some(bytes))
         }
     }
 
-    fn get_text_from_clipboard(&self) -> Option<String> {
+    fn set_data(&self, format: u32, data: &[u8]) -> Result<(), ClipboardError> {
         unsafe {
-            let mut result = None;
-
-            if OpenClipboard(None).is_ok() {
-                let hglb = GetClipboardData(ClipboardFormat::TEXT as u32);
-                if let Ok(hglb) = hglb {
-                    let pbox_copy = GlobalLock(HGLOBAL(hglb.0 as *mut c_void));
-
-                    if !pbox_copy.is_null() {
-                        result = Some(
-                            match CStr::from_ptr(pbox_copy as *const i8).to_str() {
-                                Ok(value) => value,
-                                Err(_) => {
-                                    let _ = GlobalUnlock(HGLOBAL(pbox_copy));
-                                    let _ = CloseClipboard();
-                                    return None;
-                                }
-                            }
-                            .to_string(),
-                        );
-                    }
-
-                    let _ = GlobalUnlock(HGLOBAL(hglb.0 as *mut c_void));
-                }
-                let _ = CloseClipboard();
+            let mem = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1))?;
+
+            let mem_ptr = GlobalLock(mem) as *mut u8;
+            if mem_ptr.is_null() {
+                let _ = GlobalFree(mem);
+                return Err(Error::from_win32().into());
             }
 
-            result
+            ptr::copy_nonoverlapping(data.as_ptr(), mem_ptr, data.len());
+            let _ = GlobalUnlock(mem);
+
+            // The system owns the memory once it has been handed to the clipboard
+            if let Err(error) = SetClipboardData(format, HANDLE(mem.0 as isize)) {
+                let _ = GlobalFree(mem);
+                return Err(error.into());
+            }
         }
+
+        Ok(())
     }
+}
 
-    fn set_text_from_clipboard(&mut self, text: &str) -> Result<(), Error> {
+impl Drop for OpenClipboardGuard {
+    fn drop(&mut self) {
         unsafe {
-            let mem = GlobalAlloc(GHND, (std::mem::size_of::<u8>() * (text.len() + 1)) as _)?;
+            let _ = CloseClipboard();
+        }
+    }
+}
 
-            if OpenClipboard(None).is_ok() {
-                let mem_ptr = GlobalLock(mem);
-                let mem_ptr = mem_ptr as *mut u8;
+/// Returns `false` for formats whose clipboard handle isn't an `HGLOBAL`.
+fn is_global_memory_format(format: u32) -> bool {
+    let private = CF_PRIVATEFIRST.0 as u32..=CF_PRIVATELAST.0 as u32;
+    let gdi = CF_GDIOBJFIRST.0 as u32..=CF_GDIOBJLAST.0 as u32;
+
+    !private.contains(&format)
+        && !gdi.contains(&format)
+        && !matches!(
+            ClipboardFormat::from_u16(format as u16),
+            Some(
+                ClipboardFormat::BITMAP
+                    | ClipboardFormat::DSPBITMAP
+                    | ClipboardFormat::ENHMETAFILE
+                    | ClipboardFormat::DSPENHMETAFILE
+                    | ClipboardFormat::METAFILEPICT
+                    | ClipboardFormat::DSPMETAFILEPICT
+                    | ClipboardFormat::PALETTE
+                    | ClipboardFormat::OWNERDISPLAY
+            )
+        )
+}
 
-                for (i, byte) in text.as_bytes().iter().enumerate() {
-                    ptr::write(mem_ptr.add(i), *byte);
-                }
+fn decode_text(data: &[u8]) -> Result<String, ClipboardError> {
+    let len = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
 
-                ptr::write(mem_ptr.add(text.len()), 0);
+    String::from_utf8(data[..len].to_vec())
+        .map_err(|error| ClipboardError::ConversionFailed(error.to_string()))
+}
 
-                // Empties clipboard and makes the current window the owner of the clipboard
-                let _ = EmptyClipboard();
+fn encode_text(text: &str) -> Vec<u8> {
+    text.bytes().chain([0]).collect()
+}
 
-                let _ = SetClipboardData(
-                    CF_TEXT.0 as u32,
-                    windows::Win32::Foundation::HANDLE(mem.0 as isize),
-                );
+/// Decodes `CF_TEXT` and other narrow strings, which use the ANSI code page rather than UTF-8.
+fn decode_ansi_text(data: &[u8]) -> Result<String, ClipboardError> {
+    let len = data
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(data.len());
+    let data = &data[..len];
 
-                let _ = GlobalUnlock(mem);
-                let _ = CloseClipboard();
-            }
+    if data.is_empty() {
+        return Ok(String::new());
+    }
 
-            // Free the memory when finished with it
-            let _ = GlobalFree(mem);
-            Ok(())
+    let flags = MULTI_BYTE_TO_WIDE_CHAR_FLAGS(0);
+    let wide = unsafe {
+        let len = MultiByteToWideChar(CP_ACP, flags, data, None);
+        let mut wide = vec![0; len.max(0) as usize];
+        if len <= 0 || MultiByteToWideChar(CP_ACP, flags, data, Some(&mut wide)) == 0 {
+            return Err(Error::from_win32().into());
         }
+        wide
+    };
+
+    String::from_utf16(&wide).map_err(|error| ClipboardError::ConversionFailed(error.to_string()))
+}
+
+/// Encodes text in the ANSI code page, replacing characters it lacks with `?`.
+fn encode_ansi_text(text: &str) -> Result<Vec<u8>, ClipboardError> {
+    let wide = text.encode_utf16().chain([0]).collect::<Vec<_>>();
+
+    unsafe {
+        let len = WideCharToMultiByte(CP_ACP, 0, &wide, None, PCSTR::null(), None);
+        let mut data = vec![0; len.max(0) as usize];
+        if len <= 0
+            || WideCharToMultiByte(CP_ACP, 0, &wide, Some(&mut data), PCSTR::null(), None) == 0
+        {
+            return Err(Error::from_win32().into());
+        }
+
+        Ok(data)
     }
+}
 
-    fn get_unicode_text_from_clipboard(&self) -> Option<String> {
-        unsafe {
-            let mut result = None;
-
-            if OpenClipboard(None).is_ok() {
-                let hglb = GetClipboardData(ClipboardFormat::UNICODETEXT as u32);
-                if let Ok(hglb) = hglb {
-                    let pbox_copy = GlobalLock(HGLOBAL(hglb.0 as *mut c_void));
-                    let wstr = core::slice::from_raw_parts(
-                        pbox_copy as *const u16,
-                        GlobalSize(HGLOBAL(pbox_copy)) / std::mem::size_of::<u16>(),
-                    );
-
-                    if !pbox_copy.is_null() {
-                        let size_needed = WideCharToMultiByte(CP_UTF8, 0, wstr, None, None, None);
-
-                        let mut s = vec![0u8; size_needed as usize];
-
-                        WideCharToMultiByte(CP_UTF8, 0, wstr, Some(&mut s), None, None);
-
-                        result = Some(
-                            match CStr::from_ptr(s.as_ptr() as *const i8).to_str() {
-                                Ok(value) => value,
-                                Err(_) => {
-                                    let _ = GlobalUnlock(HGLOBAL(hglb.0 as *mut c_void));
-                                    let _ = CloseClipboard();
-                                    return None;
-                                }
-                            }
-                            .to_string(),
-                        );
-                    }
-
-                    let _ = GlobalUnlock(HGLOBAL(hglb.0 as *mut c_void));
-                }
-                let _ = CloseClipboard();
-            }
+fn decode_unicode_text(data: &[u8]) -> Result<String, ClipboardError> {
+    let wide = data
+        .chunks_exact(2)
+        .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0)
+        .collect::<Vec<_>>();
+
+    String::from_utf16(&wide).map_err(|error| ClipboardError::ConversionFailed(error.to_string()))
+}
+
+fn encode_unicode_text(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_ne_bytes)
+        .collect()
+}
 
-            result
+fn format_kind(format: u32, name: Option<&str>) -> FormatKind {
+    // Windows synthesizes CF_DIB from CF_DIBV5 and CF_BITMAP, so the registry only lists CF_DIB
+    if let Some(kind) = FormatKind::from_windows_format(format, name) {
+        return kind;
+    }
+
+    match name {
+        // Markers describe the other formats rather than holding any content
+        Some(EXCLUDE_FROM_MONITOR_FORMAT | INCLUDE_IN_HISTORY_FORMAT | UPLOAD_TO_CLOUD_FORMAT) => {
+            FormatKind::Unknown
         }
+        Some(name) => FormatKind::Custom(CustomFormat::from_windows_name(name).name),
+        None => FormatKind::Unknown,
     }
+}
 
-    fn set_unicode_text_from_clipboard(&mut self, text: &str) -> Result<(), Error> {
-        unsafe {
-            if OpenClipboard(None).is_ok() {
-                let size_needed = MultiByteToWideChar(
-                    CP_UTF8,
-                    windows::Win32::Globalization::MULTI_BYTE_TO_WIDE_CHAR_FLAGS::default(),
-                    text.as_bytes(),
-                    None,
-                );
-
-                let mem = GlobalAlloc(
-                    GHND,
-                    (std::mem::size_of::<u16>() * (size_needed as usize + 1)) as _,
-                )?;
-
-                let mem_ptr = GlobalLock(mem);
-                let mem_ptr = mem_ptr as *mut u16;
-
-                for (i, c) in text.encode_utf16().enumerate() {
-                    ptr::write(mem_ptr.add(i), c);
-                }
+fn decode_item(kind: FormatKind, data: Vec<u8>) -> Result<ClipboardItem, ClipboardError> {
+    Ok(match kind {
+        FormatKind::Text => ClipboardItem::Text(decode_ansi_text(&data)?),
+        FormatKind::UnicodeText => ClipboardItem::UnicodeText(decode_unicode_text(&data)?),
+        FormatKind::FileList => ClipboardItem::FilePath(decode_file_list(&data)?),
+        FormatKind::Tiff => ClipboardItem::Tiff(Cursor::new(data)),
+        FormatKind::Html => ClipboardItem::Html(decode_html(&data)?),
+        FormatKind::Rtf => ClipboardItem::Rtf(decode_text(&data)?),
+        FormatKind::Url => ClipboardItem::Url(decode_unicode_text(&data)?),
+        FormatKind::Png => ClipboardItem::Png(Cursor::new(data)),
+        FormatKind::Pdf => ClipboardItem::Pdf(Cursor::new(data)),
+        FormatKind::Image => ClipboardItem::Image(ImageData::decode(&data, ImageFormat::Dib)?),
+        FormatKind::Custom(format) => ClipboardItem::Custom { format, data },
+        kind => return Err(ClipboardError::UnsupportedFormat(format!("{kind:?}"))),
+    })
+}
 
-                ptr::write(mem_ptr.add(text.encode_utf16().count()), 0);
+fn encode_item(item: &ClipboardItem) -> Result<(u32, Vec<u8>), ClipboardError> {
+    let data = match item {
+        ClipboardItem::Text(text) => encode_ansi_text(text)?,
+        ClipboardItem::Rtf(text) => encode_text(text),
+        ClipboardItem::UnicodeText(text) | ClipboardItem::Url(text) => encode_unicode_text(text),
+        ClipboardItem::FilePath(path) => encode_file_list(path),
+        ClipboardItem::Html(html) => encode_html(html),
+        ClipboardItem::Png(data) | ClipboardItem::Tiff(data) | ClipboardItem::Pdf(data) => {
+            data.get_ref().clone()
+        }
+        ClipboardItem::Image(image) => image.encode(ImageFormat::Dib)?,
+        ClipboardItem::Custom { format, data } => {
+            let format = register_format(&CustomFormat::named(format).windows_name)?;
+            return Ok((format, data.clone()));
+        }
+        item => {
+            return Err(ClipboardError::UnsupportedFormat(
+                item.format_name().to_owned(),
+            ))
+        }
+    };
 
-                // Empties clipboard and makes the current window the owner of the clipboard
-                let _ = EmptyClipboard();
+    let format = item
+        .kind()
+        .identifiers()
+        .and_then(|identifiers| identifiers.windows_formats.first())
+        .ok_or_else(|| ClipboardError::UnsupportedFormat(item.format_name().to_owned()))?;
 
-                let _ = SetClipboardData(
-                    CF_UNICODETEXT.0 as u32,
-                    windows::Win32::Foundation::HANDLE(mem.0 as isize),
-                );
+    let format = match *format {
+        WindowsFormat::Standard { id, .. } => id,
+        WindowsFormat::Registered(name) => register_format(name)?,
+    };
 
-                let _ = GlobalUnlock(mem);
+    Ok((format, data))
+}
 
-                // Free the memory when finished with it
-                let _ = GlobalFree(mem);
+/// Returns the id of a registered format, registering it if this is the first time it is used.
+fn register_format(name: &str) -> Result<u32, ClipboardError> {
+    let format = unsafe { RegisterClipboardFormatW(&HSTRING::from(name)) };
 
-                let _ = CloseClipboard();
-            }
-            Ok(())
+    if format == 0 {
+        return Err(Error::from_win32().into());
+    }
+
+    Ok(format)
+}
+
+/// Returns the name of a registered format, or `None` for the predefined `CF_*` formats.
+fn format_name(format: u32) -> Option<String> {
+    let mut name = [0u16; 256];
+    let len = unsafe { GetClipboardFormatNameW(format, &mut name) };
+
+    if len <= 0 {
+        return None;
+    }
+
+    Some(String::from_utf16_lossy(&name[..len as usize]))
+}
+
+fn decode_html(data: &[u8]) -> Result<String, ClipboardError> {
+    let html = decode_text(data)?;
+
+    // CF_HTML starts with a header holding the byte offsets of the copied fragment
+    let offset = |key: &str| {
+        html.lines()
+            .take_while(|line| !line.starts_with('<'))
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+    };
+
+    offset("StartFragment:")
+        .zip(offset("EndFragment:"))
+        .and_then(|(start, end)| html.get(start..end))
+        .map(str::to_owned)
+        .ok_or_else(|| ClipboardError::ConversionFailed("invalid CF_HTML header".to_owned()))
+}
+
+fn encode_html(html: &str) -> Vec<u8> {
+    const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+
+    let header = |start_html: usize,
+                  end_html: usize,
+                  start_fragment: usize,
+                  end_fragment: usize| {
+        format!(
+            "Version:0.9\r\nStartHTML:{start_html:010}\r\nEndHTML:{end_html:010}\r\nStartFragment:{start_fragment:010}\r\nEndFragment:{end_fragment:010}\r\n"
+        )
+    };
+
+    // Every offset is zero padded, so the header has the same length whatever their values are
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + html.len();
+    let end_html = end_fragment + SUFFIX.len();
+
+    let header = header(start_html, end_html, start_fragment, end_fragment);
+
+    encode_text(&format!("{header}{PREFIX}{html}{SUFFIX}"))
+}
+
+fn decode_file_list(data: &[u8]) -> Result<String, ClipboardError> {
+    if data.len() < DROPFILES_SIZE {
+        return Err(ClipboardError::ConversionFailed(
+            "invalid CF_HDROP data".to_owned(),
+        ));
+    }
+
+    let files_offset = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let wide = i32::from_ne_bytes([data[16], data[17], data[18], data[19]]) != 0;
+    let files = data.get(files_offset..).unwrap_or_default();
+
+    // Only the first path of the list is returned
+    if wide {
+        decode_unicode_text(files)
+    } else {
+        decode_ansi_text(files)
+    }
+}
+
+fn encode_file_list(path: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(DROPFILES_SIZE + (path.len() + 2) * 2);
+
+    // DROPFILES { pFiles, pt.x, pt.y, fNC, fWide }
+    data.extend((DROPFILES_SIZE as u32).to_ne_bytes());
+    data.extend(0i32.to_ne_bytes());
+    data.extend(0i32.to_ne_bytes());
+    data.extend(0i32.to_ne_bytes());
+    data.extend(1i32.to_ne_bytes());
+
+    // The list of paths is terminated by an additional NUL
+    data.extend(path.encode_utf16().chain([0, 0]).flat_map(u16::to_ne_bytes));
+
+    data
+}
+
+impl From<Error> for ClipboardError {
+    fn from(error: Error) -> Self {
+        ClipboardError::Os {
+            code: error.code().0,
+            message: error.message(),
         }
     }
 }
@@ -469,24 +934,90 @@ pub fn count_clipboard_formats() -> i32 {
 
 /// # Safety
 ///
-/// This function changes the static `CLIPBOARD_CHANGED` when called
+/// Must only be called by the system with the arguments of a message sent to the window.
+/// `WM_CLIPBOARDUPDATE` only has to wake up `wait_for_clipboard_change`, which reads the
+/// clipboard sequence number itself, so every message gets the default handling.
 pub unsafe extern "system" fn window_proc(
     param0: HWND,
     msg: u32,
     w_param: WPARAM,
     l_param: LPARAM,
 ) -> LRESULT {
-    if msg == WM_CLIPBOARDUPDATE && !CLIPBOARD_CHANGED {
-        unsafe { CLIPBOARD_CHANGED = true };
+    DefWindowProcA(param0, msg, w_param, l_param)
+}
+
+impl ClipboardBackend for WindowsCC {
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_item()
     }
 
-    DefWindowProcA(param0, msg, w_param, l_param)
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_items()
+    }
+
+    fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.get_clipboard_format(kind)
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.set_clipboard_items_with_options(items, options)
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        self.get_clipboard_options()
+    }
+
+    fn get_item_with_options(&self) -> Result<Option<(ClipboardItem, SetOptions)>, ClipboardError> {
+        self.get_clipboard_item_with_options()
+    }
+
+    fn get_items_with_options(&self) -> Result<(Vec<ClipboardItem>, SetOptions), ClipboardError> {
+        self.get_clipboard_items_with_options()
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.clear_clipboard()
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        self.get_available_formats()
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        self.snapshot_clipboard()
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.restore_clipboard(snapshot)
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        Ok(unsafe { GetClipboardSequenceNumber() }.into())
+    }
+
+    fn has_changed(&self) -> bool {
+        self.has_clipboard_changed()
+    }
+
+    fn wait_for_change(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        Ok(self.wait_for_clipboard_change(timeout))
+    }
+
+    fn factory(&self) -> BackendFactory {
+        Arc::new(|| Ok(Box::new(WindowsCC::new()?)))
+    }
 }
 
 impl Drop for WindowsCC {
     fn drop(&mut self) {
         unsafe {
             let _ = RemoveClipboardFormatListener(self.msg_only_hwnd);
+            // Fails if the clipboard was moved to another thread, which leaks the window
+            let _ = DestroyWindow(self.msg_only_hwnd);
         }
     }
 }
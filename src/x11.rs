@@ -0,0 +1,1229 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use x11rb::{
+    atom_manager,
+    connection::{Connection, RequestConnection},
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask,
+            PropMode, Property, SelectionNotifyEvent, SelectionRequestEvent, Timestamp, Window,
+            WindowClass, SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    x11_utils::X11Error,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+use crate::{
+    backend::{BackendFactory, ClipboardBackend},
+    error::ClipboardError,
+    formats::{FormatInfo, FormatKind, NativeFormat},
+    mime,
+    models::{ClipboardItem, ClipboardKind},
+    options::{SetOptions, KDE_PASSWORD_MANAGER_HINT, KDE_SECRET},
+    snapshot::{ClipboardSnapshot, SnapshotEntry},
+    watcher::NativeChange,
+};
+
+/// How long the owner of a selection has to answer a request.
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often a pending request is checked for an answer.
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// How often `wait_for_clipboard_change` checks for changes when the server doesn't send change
+/// notifications.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// The largest chunk written at once when data is sent incrementally.
+const INCR_CHUNK_SIZE: usize = 256 * 1024;
+/// The size of a `ChangeProperty` request without its data.
+const CHANGE_PROPERTY_HEADER_SIZE: usize = 24;
+
+/// Targets that describe the selection rather than holding its data.
+const META_TARGETS: &[&str] = &[
+    "TARGETS",
+    "TIMESTAMP",
+    "MULTIPLE",
+    "SAVE_TARGETS",
+    "DELETE",
+    "INSERT_SELECTION",
+    "INSERT_PROPERTY",
+];
+
+atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        TIMESTAMP,
+        MULTIPLE,
+        ATOM_PAIR,
+        INCR,
+        LIBCLIPBOARD_DATA,
+        LIBCLIPBOARD_TIMESTAMP,
+    }
+}
+
+/// The data offered for a selection, by target.
+type Offer = Vec<(Atom, Contents)>;
+
+/// The contents of a property that a selection was converted into.
+#[derive(Debug, Clone)]
+struct Contents {
+    property_type: Atom,
+    /// Whether the data is a list of 8, 16 or 32-bit values.
+    format: u8,
+    data: Vec<u8>,
+}
+
+impl Contents {
+    /// Returns text or binary data, which is written as bytes under its own type.
+    fn bytes(property_type: Atom, data: Vec<u8>) -> Self {
+        Self {
+            property_type,
+            format: 8,
+            data,
+        }
+    }
+}
+
+/// The server of the current process, if it has been started.
+static SERVER: Mutex<Option<Arc<Server>>> = Mutex::new(None);
+
+/// Owns the selections written by this process and answers requests for their data.
+///
+/// An X11 selection is only available for as long as its owner answers requests, so a single
+/// connection per process serves them from a background thread, independently of the
+/// [`X11CC`] instances that wrote them.
+struct Server {
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    /// Whether the X server notifies the window of selection owner changes through XFIXES.
+    xfixes: bool,
+    state: Mutex<ServerState>,
+    /// Signalled whenever a change count increases.
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct ServerState {
+    /// The data of every selection this process owns.
+    offers: HashMap<Atom, Offer>,
+    /// When this process last took or gave up each selection, in server time.
+    owner_times: HashMap<Atom, Timestamp>,
+    /// The latest server time read from a property change, and how many have been read.
+    server_time: (Timestamp, u64),
+    /// How often the owner of each selection changed, or without XFIXES, how often this process
+    /// wrote or cleared it or lost it to another client.
+    change_counts: HashMap<Atom, u64>,
+    /// The latest XFIXES notification for each selection.
+    native_changes: HashMap<Atom, NativeChange>,
+    /// Data being sent to requestors in chunks.
+    transfers: Vec<Transfer>,
+}
+
+/// Data too large for a single request, sent with the ICCCM `INCR` protocol: the owner writes
+/// each chunk after the requestor deletes the previous one, and ends with an empty chunk.
+struct Transfer {
+    requestor: Window,
+    property: Atom,
+    contents: Contents,
+    /// How many bytes of the data have been written.
+    sent: usize,
+}
+
+impl ServerState {
+    fn changed(&mut self, selection: Atom) {
+        *self.change_counts.entry(selection).or_default() += 1;
+    }
+
+    fn change_count(&self, selection: Atom) -> u64 {
+        self.change_counts
+            .get(&selection)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Server {
+    /// Returns the server of the current process, starting it if needed.
+    fn get() -> Result<Arc<Self>, ClipboardError> {
+        let mut server = SERVER.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(server) = &*server {
+            return Ok(server.clone());
+        }
+
+        let (conn, window, atoms) = open()?;
+        let xfixes = select_owner_changes(&conn, window, &atoms)?;
+        let started = Arc::new(Self {
+            conn,
+            window,
+            atoms,
+            xfixes,
+            state: Mutex::default(),
+            changed: Condvar::new(),
+        });
+
+        let thread_server = started.clone();
+        thread::Builder::new()
+            .name("libclipboard-x11".to_owned())
+            .spawn(move || thread_server.run())
+            .map_err(|error| ClipboardError::BackendUnavailable(error.to_string()))?;
+
+        *server = Some(started.clone());
+        Ok(started)
+    }
+
+    fn run(self: Arc<Self>) {
+        while let Ok(event) = self.conn.wait_for_event() {
+            let result = match event {
+                Event::SelectionRequest(request) => self.answer(&request),
+                Event::SelectionClear(event) if event.owner == self.window => {
+                    let owner_time = self.state().owner_times.get(&event.selection).copied();
+
+                    // A late clear of an ownership this process has since replaced
+                    let stale = match owner_time {
+                        Some(owner_time) if event.time == owner_time => self
+                            .conn
+                            .get_selection_owner(event.selection)
+                            .ok()
+                            .and_then(|cookie| cookie.reply().ok())
+                            .is_some_and(|reply| reply.owner == self.window),
+                        Some(owner_time) => is_earlier(event.time, owner_time),
+                        None => false,
+                    };
+                    if stale {
+                        continue;
+                    }
+
+                    let mut state = self.state();
+                    state.offers.remove(&event.selection);
+                    if !self.xfixes {
+                        state.changed(event.selection);
+                        self.changed.notify_all();
+                    }
+                    Ok(())
+                }
+                Event::XfixesSelectionNotify(event) => {
+                    let mut state = self.state();
+                    state.changed(event.selection);
+                    state.native_changes.insert(
+                        event.selection,
+                        NativeChange {
+                            owner: (event.owner != NONE).then_some(event.owner.into()),
+                            timestamp: event.timestamp.into(),
+                        },
+                    );
+                    self.changed.notify_all();
+                    Ok(())
+                }
+                Event::PropertyNotify(event)
+                    if event.window == self.window
+                        && event.atom == self.atoms.LIBCLIPBOARD_TIMESTAMP =>
+                {
+                    let mut state = self.state();
+                    state.server_time = (event.time, state.server_time.1 + 1);
+                    self.changed.notify_all();
+                    Ok(())
+                }
+                Event::PropertyNotify(event) if event.state == Property::DELETE => {
+                    self.send_chunk(event.window, event.atom)
+                }
+                Event::DestroyNotify(event) => {
+                    let mut state = self.state();
+                    state
+                        .transfers
+                        .retain(|transfer| transfer.requestor != event.window);
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        // The connection is gone, so the next clipboard starts a new server
+        let mut server = SERVER.lock().unwrap_or_else(PoisonError::into_inner);
+        if server
+            .as_ref()
+            .is_some_and(|server| Arc::ptr_eq(server, &self))
+        {
+            *server = None;
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn answer(&self, request: &SelectionRequestEvent) -> Result<(), ConnectionError> {
+        // Obsolete clients leave out the property and expect the target to be used instead
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+
+        let answered = if request.target == self.atoms.MULTIPLE {
+            self.write_multiple(request)?
+        } else {
+            self.write_target(
+                request.requestor,
+                request.selection,
+                request.target,
+                property,
+            )?
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if answered { property } else { NONE },
+        };
+
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+        self.conn.flush()
+    }
+
+    /// Answers a `MULTIPLE` request, whose property lists pairs of a target and the property to
+    /// write it into. The property of each target that isn't offered is replaced with `None`.
+    fn write_multiple(&self, request: &SelectionRequestEvent) -> Result<bool, ConnectionError> {
+        // The pairs are read from the property, so obsolete clients that leave it out get nothing
+        if request.property == NONE || !self.state().offers.contains_key(&request.selection) {
+            return Ok(false);
+        }
+
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                request.requestor,
+                request.property,
+                AtomEnum::ANY,
+                0,
+                u32::MAX,
+            )?
+            .reply();
+        let mut pairs: Vec<Atom> = match reply {
+            Ok(reply) => match reply.value32() {
+                Some(pairs) => pairs.collect(),
+                None => return Ok(false),
+            },
+            Err(ReplyError::ConnectionError(error)) => return Err(error),
+            // The requestor is gone
+            Err(ReplyError::X11Error(_)) => return Ok(false),
+        };
+
+        for pair in pairs.chunks_exact_mut(2) {
+            let (target, property) = (pair[0], pair[1]);
+
+            let answered = target != self.atoms.MULTIPLE
+                && property != NONE
+                && self.write_target(request.requestor, request.selection, target, property)?;
+            if !answered {
+                pair[1] = NONE;
+            }
+        }
+
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            request.property,
+            self.atoms.ATOM_PAIR,
+            &pairs,
+        )?;
+        Ok(true)
+    }
+
+    /// Writes `target` into the requestor's property, returning `false` if the target isn't
+    /// offered.
+    fn write_target(
+        &self,
+        requestor: Window,
+        selection: Atom,
+        target: Atom,
+        property: Atom,
+    ) -> Result<bool, ConnectionError> {
+        let mut state = self.state();
+        let Some(offer) = state.offers.get(&selection) else {
+            return Ok(false);
+        };
+
+        if target == self.atoms.TARGETS {
+            let targets: Vec<Atom> = [
+                self.atoms.TARGETS,
+                self.atoms.TIMESTAMP,
+                self.atoms.MULTIPLE,
+            ]
+            .into_iter()
+            .chain(offer.iter().map(|(target, _)| *target))
+            .collect();
+
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                AtomEnum::ATOM,
+                &targets,
+            )?;
+            return Ok(true);
+        }
+
+        if target == self.atoms.TIMESTAMP {
+            // The time this process took the selection, which requestors compare between owners
+            let Some(&time) = state.owner_times.get(&selection) else {
+                return Ok(false);
+            };
+
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                AtomEnum::INTEGER,
+                &[time],
+            )?;
+            return Ok(true);
+        }
+
+        let Some((_, contents)) = offer.iter().find(|(offered, _)| *offered == target) else {
+            return Ok(false);
+        };
+
+        if contents.data.len() > self.chunk_size() {
+            let transfer = Transfer {
+                requestor,
+                property,
+                contents: contents.clone(),
+                sent: 0,
+            };
+
+            // The requestor deleting the property asks for the next chunk
+            self.conn.change_window_attributes(
+                requestor,
+                &ChangeWindowAttributesAux::new()
+                    .event_mask(EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY),
+            )?;
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                requestor,
+                property,
+                self.atoms.INCR,
+                &[u32::try_from(transfer.contents.data.len()).unwrap_or(u32::MAX)],
+            )?;
+
+            state.transfers.push(transfer);
+            return Ok(true);
+        }
+
+        self.write_property(requestor, property, contents, &contents.data)?;
+        Ok(true)
+    }
+
+    /// Replaces `property` with `data`, which is all or part of the data of `contents`.
+    fn write_property(
+        &self,
+        window: Window,
+        property: Atom,
+        contents: &Contents,
+        data: &[u8],
+    ) -> Result<(), ConnectionError> {
+        // The data is a whole number of values
+        let unit = usize::from(contents.format / 8);
+        let len = data.len() / unit;
+
+        self.conn.change_property(
+            PropMode::REPLACE,
+            window,
+            property,
+            contents.property_type,
+            contents.format,
+            u32::try_from(len).unwrap_or(u32::MAX),
+            &data[..len * unit],
+        )?;
+        Ok(())
+    }
+
+    /// Writes the next chunk of the transfer into `property`, once the requestor has deleted
+    /// the previous one.
+    fn send_chunk(&self, requestor: Window, property: Atom) -> Result<(), ConnectionError> {
+        let chunk_size = self.chunk_size();
+        let mut state = self.state();
+
+        let Some(index) = state
+            .transfers
+            .iter()
+            .position(|transfer| transfer.requestor == requestor && transfer.property == property)
+        else {
+            return Ok(());
+        };
+
+        let transfer = &mut state.transfers[index];
+        let end = transfer.contents.data.len().min(transfer.sent + chunk_size);
+
+        self.write_property(
+            requestor,
+            property,
+            &transfer.contents,
+            &transfer.contents.data[transfer.sent..end],
+        )?;
+
+        // The empty chunk after the data ends the transfer
+        if transfer.sent == transfer.contents.data.len() {
+            state.transfers.remove(index);
+
+            if !state
+                .transfers
+                .iter()
+                .any(|transfer| transfer.requestor == requestor)
+            {
+                self.conn.change_window_attributes(
+                    requestor,
+                    &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+                )?;
+            }
+        } else {
+            transfer.sent = end;
+        }
+
+        self.conn.flush()
+    }
+
+    /// Returns the largest amount of data written into a property at once.
+    fn chunk_size(&self) -> usize {
+        INCR_CHUNK_SIZE.min(self.conn.maximum_request_bytes() - CHANGE_PROPERTY_HEADER_SIZE)
+    }
+
+    /// Returns the current server time, which ICCCM requires instead of `CurrentTime` when
+    /// taking a selection.
+    ///
+    /// The time is read from the notification of an empty append to a property of the window.
+    fn server_time(&self) -> Result<Timestamp, ClipboardError> {
+        let read = self.state().server_time.1;
+
+        self.conn.change_property8(
+            PropMode::APPEND,
+            self.window,
+            self.atoms.LIBCLIPBOARD_TIMESTAMP,
+            AtomEnum::STRING,
+            &[],
+        )?;
+        self.conn.flush()?;
+
+        let (state, wait) = self
+            .changed
+            .wait_timeout_while(self.state(), READ_TIMEOUT, |state| {
+                state.server_time.1 == read
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if wait.timed_out() {
+            return Err(ClipboardError::Timeout);
+        }
+
+        Ok(state.server_time.0)
+    }
+
+    fn set(&self, selection: Atom, offer: Offer) -> Result<(), ClipboardError> {
+        let change_count = self.change_count(selection);
+        let time = self.server_time()?;
+        {
+            let mut state = self.state();
+            state.offers.insert(selection, offer);
+            state.owner_times.insert(selection, time);
+        }
+
+        self.conn
+            .set_selection_owner(self.window, selection, time)?;
+        let owner = self.conn.get_selection_owner(selection)?.reply()?.owner;
+
+        if owner != self.window {
+            self.state().offers.remove(&selection);
+            return Err(ClipboardError::ClipboardBusy);
+        }
+
+        self.count_own_change(selection, change_count);
+        Ok(())
+    }
+
+    fn clear(&self, selection: Atom) -> Result<bool, ClipboardError> {
+        let change_count = self.change_count(selection);
+        let owned = self.conn.get_selection_owner(selection)?.reply()?.owner == self.window;
+        let time = self.server_time()?;
+
+        // Any client may give up a selection on behalf of its owner, which is told to drop it
+        self.conn.set_selection_owner(NONE, selection, time)?;
+        self.conn.flush()?;
+
+        let mut state = self.state();
+        state.offers.remove(&selection);
+        state.owner_times.insert(selection, time);
+        drop(state);
+        self.count_own_change(selection, change_count);
+        Ok(owned)
+    }
+
+    /// Counts a change this process made to the selection, which had `change_count` before.
+    ///
+    /// With XFIXES the change is counted when its notification arrives, so this waits for it
+    /// to keep the change count consistent with what the caller did.
+    fn count_own_change(&self, selection: Atom, change_count: u64) {
+        let state = self.state();
+
+        let mut state = if self.xfixes {
+            let (state, wait) = self
+                .changed
+                .wait_timeout_while(state, READ_TIMEOUT, |state| {
+                    state.change_count(selection) == change_count
+                })
+                .unwrap_or_else(PoisonError::into_inner);
+
+            if !wait.timed_out() {
+                return;
+            }
+            state
+        } else {
+            state
+        };
+
+        state.changed(selection);
+        self.changed.notify_all();
+    }
+
+    fn change_count(&self, selection: Atom) -> u64 {
+        self.state().change_count(selection)
+    }
+
+    /// Blocks until the change count of `selection` differs from `change_count` or `timeout`
+    /// elapses.
+    fn wait_for_change(&self, selection: Atom, change_count: u64, timeout: Duration) {
+        let _ = self
+            .changed
+            .wait_timeout_while(self.state(), timeout, |state| {
+                state.change_count(selection) == change_count
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+/// Asks the X server to notify `window` whenever the owner of a selection changes, returning
+/// `false` if it doesn't support the XFIXES extension.
+fn select_owner_changes(
+    conn: &RustConnection,
+    window: Window,
+    atoms: &Atoms,
+) -> Result<bool, ClipboardError> {
+    match conn.xfixes_query_version(5, 0) {
+        Ok(version) => version.reply()?,
+        Err(ConnectionError::UnsupportedExtension) => return Ok(false),
+        Err(error) => return Err(error.into()),
+    };
+
+    let events = SelectionEventMask::SET_SELECTION_OWNER
+        | SelectionEventMask::SELECTION_WINDOW_DESTROY
+        | SelectionEventMask::SELECTION_CLIENT_CLOSE;
+
+    for selection in [
+        atoms.CLIPBOARD,
+        AtomEnum::PRIMARY.into(),
+        AtomEnum::SECONDARY.into(),
+    ] {
+        conn.xfixes_select_selection_input(window, selection, events)?;
+    }
+
+    conn.flush()?;
+    Ok(true)
+}
+
+/// Returns whether server time `time` is before `other`, allowing for the clock wrapping
+/// around after about 49 days.
+fn is_earlier(time: Timestamp, other: Timestamp) -> bool {
+    (time.wrapping_sub(other) as i32) < 0
+}
+
+/// Connects to the display in `$DISPLAY` and creates a hidden window to own selections and
+/// receive their data.
+fn open() -> Result<(RustConnection, Window, Atoms), ClipboardError> {
+    let (conn, screen) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen].root;
+    let window = conn.generate_id()?;
+
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+
+    let atoms = Atoms::new(&conn)?.reply()?;
+    conn.flush()?;
+
+    Ok((conn, window, atoms))
+}
+
+/// A selection on an X11 display, which is `CLIPBOARD`, `PRIMARY` or `SECONDARY`.
+///
+/// Data written by any instance is served by a background thread for as long as the process
+/// owns the selection. Changes are tracked through XFIXES notifications of new owners; without
+/// XFIXES, only the changes this process can see are counted: its own writes and other clients
+/// taking the selection over.
+pub struct X11CC {
+    kind: ClipboardKind,
+    selection: Atom,
+    server: Arc<Server>,
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    atom_names: RefCell<HashMap<Atom, String>>,
+    /// The owner and change count of the selection when `has_clipboard_changed` was last called.
+    /// The owner is only tracked without XFIXES.
+    last_change: Cell<(Window, u64)>,
+}
+
+impl fmt::Debug for X11CC {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X11CC")
+            .field("kind", &self.kind)
+            .field("selection", &self.selection)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl X11CC {
+    pub fn new() -> Result<Self, ClipboardError> {
+        Self::with_kind(ClipboardKind::Clipboard)
+    }
+
+    /// Opens the selection for `kind`. The macOS pasteboards don't exist on X11.
+    pub fn with_kind(kind: ClipboardKind) -> Result<Self, ClipboardError> {
+        let selection = match kind {
+            ClipboardKind::Clipboard => None,
+            ClipboardKind::Primary => Some(AtomEnum::PRIMARY.into()),
+            ClipboardKind::Secondary => Some(AtomEnum::SECONDARY.into()),
+            ClipboardKind::Find | ClipboardKind::Named(_) => {
+                return Err(ClipboardError::UnsupportedKind(kind))
+            }
+        };
+
+        let server = Server::get()?;
+        let (conn, window, atoms) = open()?;
+
+        let clipboard = Self {
+            kind,
+            selection: selection.unwrap_or(atoms.CLIPBOARD),
+            server,
+            conn,
+            window,
+            atoms,
+            atom_names: RefCell::default(),
+            last_change: Cell::new((NONE, 0)),
+        };
+        clipboard.last_change.set(clipboard.current_change()?);
+
+        Ok(clipboard)
+    }
+
+    pub fn has_clipboard_changed(&self) -> bool {
+        match self.current_change() {
+            Ok(change) => change != self.last_change.replace(change),
+            Err(_) => false,
+        }
+    }
+
+    /// Blocks until [`has_clipboard_changed`](Self::has_clipboard_changed) would return `true`
+    /// or `timeout` elapses, returning whether the selection changed.
+    pub fn wait_for_clipboard_change(&self, timeout: Duration) -> bool {
+        if self.server.xfixes {
+            let (_, change_count) = self.last_change.get();
+            self.server
+                .wait_for_change(self.selection, change_count, timeout);
+            return self.has_clipboard_changed();
+        }
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.has_clipboard_changed() {
+                return true;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+
+            thread::sleep(remaining.min(WAIT_POLL_INTERVAL));
+        }
+    }
+
+    fn current_change(&self) -> Result<(Window, u64), ClipboardError> {
+        let change_count = self.server.change_count(self.selection);
+
+        if self.server.xfixes {
+            return Ok((NONE, change_count));
+        }
+
+        Ok((self.owner()?, change_count))
+    }
+
+    fn owner(&self) -> Result<Window, ClipboardError> {
+        Ok(self
+            .conn
+            .get_selection_owner(self.selection)?
+            .reply()?
+            .owner)
+    }
+
+    fn atom(&self, name: &str) -> Result<Atom, ClipboardError> {
+        let cached = self
+            .atom_names
+            .borrow()
+            .iter()
+            .find_map(|(&atom, cached)| (cached == name).then_some(atom));
+
+        if let Some(atom) = cached {
+            return Ok(atom);
+        }
+
+        let atom = self.conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+        self.atom_names.borrow_mut().insert(atom, name.to_owned());
+        Ok(atom)
+    }
+
+    fn atom_name(&self, atom: Atom) -> Result<String, ClipboardError> {
+        if let Some(name) = self.atom_names.borrow().get(&atom) {
+            return Ok(name.clone());
+        }
+
+        let name = self.conn.get_atom_name(atom)?.reply()?.name;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        self.atom_names.borrow_mut().insert(atom, name.clone());
+        Ok(name)
+    }
+
+    /// Asks the owner of the selection to convert it to `target`, returning the property it
+    /// wrote, or `None` if the owner can't convert it.
+    fn convert(&self, target: Atom) -> Result<Option<Contents>, ClipboardError> {
+        self.conn.convert_selection(
+            self.window,
+            self.selection,
+            target,
+            self.atoms.LIBCLIPBOARD_DATA,
+            CURRENT_TIME,
+        )?;
+        self.conn.flush()?;
+
+        let property = self.wait_for(|event| match event {
+            Event::SelectionNotify(event)
+                if event.selection == self.selection && event.target == target =>
+            {
+                Some(event.property)
+            }
+            _ => None,
+        })?;
+
+        if property == NONE {
+            return Ok(None);
+        }
+
+        let reply = self
+            .conn
+            .get_property(true, self.window, property, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        if reply.type_ == self.atoms.INCR {
+            return self.receive_chunks(property).map(Some);
+        }
+
+        Ok(Some(Contents {
+            property_type: reply.type_,
+            format: reply.format,
+            data: reply.value,
+        }))
+    }
+
+    /// Receives data sent with the `INCR` protocol, reading and deleting each chunk the owner
+    /// writes into `property` until an empty one.
+    fn receive_chunks(&self, property: Atom) -> Result<Contents, ClipboardError> {
+        let mut contents = Contents::bytes(NONE, Vec::new());
+
+        loop {
+            self.wait_for(|event| match event {
+                Event::PropertyNotify(event)
+                    if event.window == self.window
+                        && event.atom == property
+                        && event.state == Property::NEW_VALUE =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })?;
+
+            let reply = self
+                .conn
+                .get_property(true, self.window, property, AtomEnum::ANY, 0, u32::MAX)?
+                .reply()?;
+            self.conn.flush()?;
+
+            if reply.value.is_empty() {
+                return Ok(contents);
+            }
+
+            contents.property_type = reply.type_;
+            contents.format = reply.format;
+            contents.data.extend_from_slice(&reply.value);
+        }
+    }
+
+    /// Waits for the owner of the selection to send an event that `matches` accepts.
+    fn wait_for<T>(
+        &self,
+        mut matches: impl FnMut(Event) -> Option<T>,
+    ) -> Result<T, ClipboardError> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+
+        loop {
+            match self.conn.poll_for_event()? {
+                Some(event) => {
+                    if let Some(value) = matches(event) {
+                        return Ok(value);
+                    }
+                }
+                None if Instant::now() >= deadline => return Err(ClipboardError::Timeout),
+                None => thread::sleep(READ_POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Returns the targets the selection can be converted to, with their names.
+    fn targets(&self) -> Result<Vec<(Atom, String)>, ClipboardError> {
+        if self.owner()? == NONE {
+            return Ok(Vec::new());
+        }
+
+        let Some(contents) = self.convert(self.atoms.TARGETS)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut targets = Vec::new();
+
+        for chunk in contents.data.chunks_exact(4) {
+            let atom = Atom::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            if atom == NONE || targets.iter().any(|(target, _)| *target == atom) {
+                continue;
+            }
+
+            let name = self.atom_name(atom)?;
+            if !META_TARGETS.contains(&name.as_str()) {
+                targets.push((atom, name));
+            }
+        }
+
+        Ok(targets)
+    }
+
+    /// Reads the first of `targets` that holds `kind`, trying the targets in the order of the
+    /// format registry.
+    fn read_format(
+        &self,
+        targets: &[(Atom, String)],
+        kind: &FormatKind,
+    ) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let mut candidates: Vec<_> = targets
+            .iter()
+            .filter(|(_, name)| mime::holds(name, kind))
+            .collect();
+        candidates.sort_by_key(|(_, name)| mime::preference(kind, name));
+
+        for (atom, name) in candidates {
+            let Some(contents) = self.convert(*atom)? else {
+                continue;
+            };
+
+            // A URI list holds either URLs or files
+            match mime::decode_item(name, contents.data) {
+                Some(item) if item.kind() == *kind => return Ok(Some(item)),
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the kinds of `targets` in the order they are offered.
+    fn kinds(targets: &[(Atom, String)]) -> Vec<FormatKind> {
+        mime::offered_kinds(targets.iter().map(|(_, name)| name.as_str()))
+    }
+
+    /// Adds a target to `offer` unless it is already there.
+    fn offer_target(
+        &self,
+        offer: &mut Offer,
+        target: &str,
+        data: Vec<u8>,
+    ) -> Result<(), ClipboardError> {
+        let atom = self.atom(target)?;
+
+        // `TEXT` is answered with its most basic encoding; every other target is its own type
+        let property_type = if target == "TEXT" {
+            AtomEnum::STRING.into()
+        } else {
+            atom
+        };
+
+        Self::offer_contents(offer, atom, Contents::bytes(property_type, data));
+        Ok(())
+    }
+
+    /// Adds a target to `offer` with the given contents unless it is already there.
+    fn offer_contents(offer: &mut Offer, target: Atom, contents: Contents) {
+        if !offer.iter().any(|(existing, _)| *existing == target) {
+            offer.push((target, contents));
+        }
+    }
+}
+
+impl ClipboardBackend for X11CC {
+    fn kind(&self) -> ClipboardKind {
+        self.kind.clone()
+    }
+
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        let targets = self.targets()?;
+
+        for kind in Self::kinds(&targets) {
+            if let Some(item) = self.read_format(&targets, &kind)? {
+                return Ok(Some(item));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        let targets = self.targets()?;
+        let mut items: Vec<ClipboardItem> = Vec::new();
+
+        for kind in Self::kinds(&targets) {
+            if let Some(item) = self.read_format(&targets, &kind)? {
+                if !items.iter().any(|existing| existing.kind() == item.kind()) {
+                    items.push(item);
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    fn get_format(&self, kind: &FormatKind) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.read_format(&self.targets()?, kind)
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        let mut offer = Offer::new();
+
+        for item in items {
+            for target in mime::item_targets(item) {
+                self.offer_target(&mut offer, &target, mime::encode_item(item, &target)?)?;
+            }
+        }
+
+        if options.concealed || options.exclude_from_history {
+            self.offer_target(&mut offer, KDE_PASSWORD_MANAGER_HINT, KDE_SECRET.to_vec())?;
+        }
+
+        self.server.set(self.selection, offer)
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        let hint = self
+            .targets()?
+            .into_iter()
+            .find(|(_, name)| name == KDE_PASSWORD_MANAGER_HINT);
+
+        let secret = match hint {
+            Some((atom, _)) => self
+                .convert(atom)?
+                .is_some_and(|contents| contents.data == KDE_SECRET),
+            None => false,
+        };
+
+        Ok(SetOptions {
+            concealed: secret,
+            exclude_from_history: secret,
+            ..SetOptions::default()
+        })
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.server.clear(self.selection)
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        Ok(self
+            .targets()?
+            .into_iter()
+            .map(|(_, name)| FormatInfo {
+                kind: mime::target_kind(&name),
+                native: NativeFormat::X11Target(name),
+                size: None,
+            })
+            .collect())
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        let mut entries = Vec::new();
+
+        // The type and format are kept, as owners may answer a target in several encodings
+        for (atom, name) in self.targets()? {
+            if let Some(contents) = self.convert(atom)? {
+                entries.push(SnapshotEntry {
+                    native: NativeFormat::X11Property {
+                        target: name,
+                        property_type: self.atom_name(contents.property_type)?,
+                        format: contents.format,
+                    },
+                    data: contents.data,
+                });
+            }
+        }
+
+        Ok(ClipboardSnapshot { entries })
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        let mut offer = Offer::new();
+
+        for entry in &snapshot.entries {
+            match &entry.native {
+                NativeFormat::X11Target(target) => {
+                    self.offer_target(&mut offer, target, entry.data.clone())?
+                }
+                NativeFormat::X11Property { format, .. } if ![8, 16, 32].contains(format) => {
+                    return Err(ClipboardError::UnsupportedFormat(format!(
+                        "{:?}",
+                        entry.native
+                    )))
+                }
+                NativeFormat::X11Property {
+                    target,
+                    property_type,
+                    format,
+                } => Self::offer_contents(
+                    &mut offer,
+                    self.atom(target)?,
+                    Contents {
+                        property_type: self.atom(property_type)?,
+                        format: *format,
+                        data: entry.data.clone(),
+                    },
+                ),
+                native => return Err(ClipboardError::UnsupportedFormat(format!("{native:?}"))),
+            }
+        }
+
+        self.server.set(self.selection, offer)
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        Ok(self.server.change_count(self.selection))
+    }
+
+    fn has_changed(&self) -> bool {
+        self.has_clipboard_changed()
+    }
+
+    fn wait_for_change(&self, timeout: Duration) -> Result<bool, ClipboardError> {
+        Ok(self.wait_for_clipboard_change(timeout))
+    }
+
+    fn last_native_change(&self) -> Option<NativeChange> {
+        self.server
+            .state()
+            .native_changes
+            .get(&self.selection)
+            .copied()
+    }
+
+    fn factory(&self) -> BackendFactory {
+        let kind = self.kind.clone();
+
+        Arc::new(move || Ok(Box::new(X11CC::with_kind(kind.clone())?)))
+    }
+}
+
+impl Drop for X11CC {
+    fn drop(&mut self) {
+        let _ = self.conn.destroy_window(self.window);
+        let _ = self.conn.flush();
+    }
+}
+
+impl From<ConnectError> for ClipboardError {
+    fn from(error: ConnectError) -> Self {
+        Self::BackendUnavailable(error.to_string())
+    }
+}
+
+impl From<ConnectionError> for ClipboardError {
+    fn from(error: ConnectionError) -> Self {
+        Self::BackendUnavailable(error.to_string())
+    }
+}
+
+impl From<X11Error> for ClipboardError {
+    fn from(error: X11Error) -> Self {
+        Self::Os {
+            code: error.error_code.into(),
+            message: format!("{:?}", error.error_kind),
+        }
+    }
+}
+
+impl From<ReplyError> for ClipboardError {
+    fn from(error: ReplyError) -> Self {
+        match error {
+            ReplyError::ConnectionError(error) => error.into(),
+            ReplyError::X11Error(error) => error.into(),
+        }
+    }
+}
+
+impl From<ReplyOrIdError> for ClipboardError {
+    fn from(error: ReplyOrIdError) -> Self {
+        match error {
+            ReplyOrIdError::ConnectionError(error) => error.into(),
+            ReplyOrIdError::X11Error(error) => error.into(),
+            ReplyOrIdError::IdsExhausted => Self::BackendUnavailable(error.to_string()),
+        }
+    }
+}
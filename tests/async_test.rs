@@ -0,0 +1,49 @@
+#![cfg(feature = "async")]
+
+use std::time::Duration;
+
+use futures::{executor::block_on, StreamExt};
+use libclipboard::{
+    memory::MemoryClipboard, AsyncClipboard, Clipboard, ClipboardBackend, ClipboardItem,
+};
+
+#[test]
+fn test_get_and_set() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let clipboard =
+        AsyncClipboard::from_factory(Clipboard::from_backend(backend).factory()).unwrap();
+
+    block_on(async {
+        clipboard
+            .set_item(ClipboardItem::Text("Hello World!".to_owned()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Some(ClipboardItem::Text("Hello World!".to_owned())),
+            clipboard.get_item().await.unwrap()
+        );
+    });
+
+    assert_eq!(
+        Some(ClipboardItem::Text("Hello World!".to_owned())),
+        other.get_item().unwrap()
+    );
+}
+
+#[test]
+fn test_changes_stream() {
+    let backend = MemoryClipboard::new();
+    let mut writer = backend.new_handle();
+    let clipboard = Clipboard::from_backend(backend).to_async().unwrap();
+    let mut changes = clipboard.changes().unwrap();
+
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        writer.clear().unwrap();
+    });
+
+    let change = block_on(changes.next());
+    assert!(change.is_some());
+}
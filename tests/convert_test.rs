@@ -0,0 +1,187 @@
+use std::io::Cursor;
+
+use libclipboard::{
+    memory::MemoryClipboard, Clipboard, ClipboardItem, FormatKind, GetOptions, ImageData,
+    ImageFormat, SetOptions, WriteOptions,
+};
+
+fn text(item: &ClipboardItem) -> String {
+    match item.convert(&FormatKind::UnicodeText).unwrap() {
+        Some(ClipboardItem::UnicodeText(text)) => text,
+        item => panic!("unexpected conversion: {item:?}"),
+    }
+}
+
+fn tiff() -> ClipboardItem {
+    let image = ImageData::new(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+    ClipboardItem::Tiff(Cursor::new(image.encode(ImageFormat::Tiff).unwrap()))
+}
+
+#[test]
+fn test_html_to_text() {
+    let html = "<html><head><title>Ignored</title><style>p { color: red }</style></head>\
+        <body><h1>Title</h1><p>Hello <b>bold</b>\n   world</p><!-- a comment -->\
+        <ul><li>one</li><li>two&nbsp;&amp;&#32;&#x33;</li></ul>a<br>b<script>x()</script></body></html>";
+
+    assert_eq!(
+        "Title\nHello bold world\none\ntwo & 3\na\nb",
+        text(&ClipboardItem::Html(html.to_owned()))
+    );
+    assert_eq!(
+        "unbroken",
+        text(&ClipboardItem::Html("un<i>broken</i>".to_owned()))
+    );
+}
+
+#[test]
+fn test_rtf_to_text() {
+    let rtf = r"{\rtf1\ansi\deff0{\fonttbl{\f0\fswiss Helvetica;}}{\colortbl;\red255\green0\blue0;}
+{\*\generator Word;}\f0\pard Hello {\b bold} world\par
+Caf\'e9 \u8364? \{braces\}\tab end\par}";
+
+    assert_eq!(
+        "Hello bold world\nCafé € {braces}\tend",
+        text(&ClipboardItem::Rtf(rtf.to_owned()))
+    );
+
+    // Escaped bytes are in Windows-1252, which puts punctuation where Latin-1 has controls
+    assert_eq!(
+        "“Hi” – 5€",
+        text(&ClipboardItem::Rtf(
+            r"{\rtf1\ansi \'93Hi\'94 \'96 5\'80}".to_owned()
+        ))
+    );
+    assert_eq!(
+        "“Hi”",
+        text(&ClipboardItem::Rtf(r"{\rtf1\ansi \'93Hi\'94}".to_owned()))
+    );
+
+    // Code points above U+FFFF are written as surrogate pairs, each with its own fallback
+    assert_eq!(
+        "Hello 😀 world",
+        text(&ClipboardItem::Rtf(
+            r"{\rtf1 Hello \u-10179?\u-8704? world}".to_owned()
+        ))
+    );
+    assert_eq!(
+        "😀!",
+        text(&ClipboardItem::Rtf(
+            r"{\rtf1\uc2 \u-10179??\u-8704??!}".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn test_other_conversions() {
+    assert_eq!(
+        "https://example.com",
+        text(&ClipboardItem::Url("https://example.com".to_owned()))
+    );
+    assert_eq!(
+        "/tmp/hello.txt",
+        text(&ClipboardItem::FilePath("/tmp/hello.txt".to_owned()))
+    );
+    assert_eq!(
+        Some(ClipboardItem::Text("Hi".to_owned())),
+        ClipboardItem::UnicodeText("Hi".to_owned())
+            .convert(&FormatKind::Text)
+            .unwrap()
+    );
+    assert_eq!(
+        None,
+        ClipboardItem::Html("<b>Hi</b>".to_owned())
+            .convert(&FormatKind::Png)
+            .unwrap()
+    );
+
+    let Some(ClipboardItem::Png(png)) = tiff().convert(&FormatKind::Png).unwrap() else {
+        panic!("TIFF should convert to PNG");
+    };
+    assert_eq!(
+        vec![255, 0, 0, 255, 0, 0, 255, 128],
+        ImageData::decode(png.get_ref(), ImageFormat::Png)
+            .unwrap()
+            .rgba
+    );
+}
+
+#[test]
+fn test_read_synthesizes_missing_formats() {
+    let backend = MemoryClipboard::new();
+    let mut writer = Clipboard::from_backend(backend.new_handle());
+    let clipboard = Clipboard::from_backend(backend);
+    let no_fallbacks = WriteOptions { fallbacks: false };
+
+    writer
+        .set_items_with_write_options(
+            &[ClipboardItem::Html("<p>Hi</p>".to_owned())],
+            &SetOptions::default(),
+            &no_fallbacks,
+        )
+        .unwrap();
+    assert_eq!(1, clipboard.get_items().unwrap().len());
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Hi".to_owned())),
+        clipboard.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+    assert_eq!(
+        None,
+        clipboard
+            .get_format_with_options(&FormatKind::UnicodeText, &GetOptions { synthesize: false })
+            .unwrap()
+    );
+    assert_eq!(
+        Some(ClipboardItem::Text("Hi".to_owned())),
+        clipboard
+            .get_preferred(&[FormatKind::Png, FormatKind::Text])
+            .unwrap()
+    );
+
+    writer
+        .set_items_with_write_options(&[tiff()], &SetOptions::default(), &no_fallbacks)
+        .unwrap();
+    assert!(matches!(
+        clipboard.get_format(&FormatKind::Png).unwrap(),
+        Some(ClipboardItem::Png(_))
+    ));
+    assert!(matches!(
+        clipboard.get_format(&FormatKind::Image).unwrap(),
+        Some(ClipboardItem::Image(_))
+    ));
+}
+
+#[test]
+fn test_write_adds_fallbacks() {
+    let mut clipboard = Clipboard::in_memory();
+
+    clipboard
+        .set_item(ClipboardItem::Html("<p>Hi &amp; bye</p>".to_owned()))
+        .unwrap();
+    assert_eq!(
+        vec![
+            ClipboardItem::Html("<p>Hi &amp; bye</p>".to_owned()),
+            ClipboardItem::UnicodeText("Hi & bye".to_owned()),
+        ],
+        clipboard.get_items().unwrap()
+    );
+
+    // Existing plain text is kept as it is
+    clipboard
+        .set_items(&[
+            ClipboardItem::Rtf(r"{\rtf1 Rich}".to_owned()),
+            ClipboardItem::Text("Plain".to_owned()),
+        ])
+        .unwrap();
+    assert_eq!(2, clipboard.get_items().unwrap().len());
+
+    clipboard.set_item(tiff()).unwrap();
+    assert_eq!(
+        vec![FormatKind::Tiff, FormatKind::Png],
+        clipboard
+            .get_items()
+            .unwrap()
+            .iter()
+            .map(ClipboardItem::kind)
+            .collect::<Vec<_>>()
+    );
+}
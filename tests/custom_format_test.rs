@@ -0,0 +1,46 @@
+use libclipboard::{Clipboard, ClipboardItem, CustomFormat};
+
+#[test]
+fn test_unregistered_format_uses_its_name_everywhere() {
+    let format = CustomFormat::named("com.example.unregistered");
+
+    assert_eq!("com.example.unregistered", format.mime_type);
+    assert_eq!("com.example.unregistered", format.uti);
+    assert_eq!("com.example.unregistered", format.windows_name);
+}
+
+#[test]
+fn test_registered_format_lookup() {
+    CustomFormat::new("shape")
+        .with_mime_type("application/x-example-shape")
+        .with_uti("com.example.shape")
+        .with_windows_name("Example Shape")
+        .register();
+
+    assert_eq!("com.example.shape", CustomFormat::named("shape").uti);
+    assert_eq!(
+        "shape",
+        CustomFormat::from_mime_type("application/x-example-shape").name
+    );
+    assert_eq!("shape", CustomFormat::from_uti("com.example.shape").name);
+    assert_eq!(
+        "shape",
+        CustomFormat::from_windows_name("Example Shape").name
+    );
+}
+
+#[test]
+fn test_custom_item_round_trip() {
+    let mut clipboard = Clipboard::in_memory();
+    let item = ClipboardItem::Custom {
+        format: "com.example.round-trip".to_owned(),
+        data: vec![0, 1, 2, 3],
+    };
+
+    clipboard
+        .set_items(&[item.clone(), ClipboardItem::Text("fallback".to_owned())])
+        .unwrap();
+
+    assert_eq!(Some(item), clipboard.get_item().unwrap());
+    assert_eq!(2, clipboard.available_formats().unwrap().len());
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use libclipboard::{memory::MemoryClipboard, Clipboard, ClipboardItem};
+
+#[test]
+fn test_expired_item_is_cleared() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    let handle = clipboard
+        .set_item_with_expiry(
+            ClipboardItem::UnicodeText("hunter2".to_owned()),
+            Duration::from_millis(50),
+        )
+        .unwrap();
+    assert!(other.get_item().unwrap().is_some());
+
+    // The clear doesn't depend on the clipboard that scheduled it
+    drop(clipboard);
+
+    assert!(handle.wait().unwrap());
+    assert_eq!(None, other.get_item().unwrap());
+}
+
+#[test]
+fn test_replaced_item_is_kept() {
+    let backend = MemoryClipboard::new();
+    let mut other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    let handle = clipboard
+        .set_item_with_expiry(
+            ClipboardItem::UnicodeText("hunter2".to_owned()),
+            Duration::from_millis(50),
+        )
+        .unwrap();
+    other
+        .set_item(ClipboardItem::Text("Replacement".to_owned()))
+        .unwrap();
+
+    assert!(!handle.wait().unwrap());
+    assert_eq!(
+        Some(ClipboardItem::Text("Replacement".to_owned())),
+        clipboard.get_item().unwrap()
+    );
+}
+
+#[test]
+fn test_cancel() {
+    let mut clipboard = Clipboard::in_memory();
+
+    let handle = clipboard
+        .set_item_with_expiry(
+            ClipboardItem::UnicodeText("hunter2".to_owned()),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    handle.cancel();
+
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("hunter2".to_owned())),
+        clipboard.get_item().unwrap()
+    );
+}
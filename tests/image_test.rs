@@ -0,0 +1,91 @@
+use libclipboard::{
+    memory::MemoryClipboard, Clipboard, ClipboardError, ClipboardItem, FormatKind, ImageData,
+    ImageFormat,
+};
+
+fn screenshot() -> ImageData {
+    let rgba = (0..4 * 3)
+        .flat_map(|pixel| [pixel * 20, 255 - pixel * 20, 128, 255 - pixel])
+        .collect();
+
+    ImageData {
+        dpi: Some(144),
+        ..ImageData::new(4, 3, rgba).unwrap()
+    }
+}
+
+#[test]
+fn test_lossless_round_trips() {
+    let image = screenshot();
+
+    for format in [
+        ImageFormat::Png,
+        ImageFormat::Tiff,
+        ImageFormat::Bmp,
+        ImageFormat::Dib,
+    ] {
+        let decoded = ImageData::decode(&image.encode(format).unwrap(), format).unwrap();
+
+        assert_eq!(image.width, decoded.width, "{format:?}");
+        assert_eq!(image.height, decoded.height, "{format:?}");
+        assert_eq!(image.rgba, decoded.rgba, "{format:?}");
+    }
+}
+
+#[test]
+fn test_dpi_is_kept() {
+    let image = screenshot();
+
+    for format in [ImageFormat::Png, ImageFormat::Bmp, ImageFormat::Dib] {
+        let decoded = ImageData::decode(&image.encode(format).unwrap(), format).unwrap();
+        assert_eq!(Some(144), decoded.dpi, "{format:?}");
+    }
+}
+
+#[test]
+fn test_jpeg() {
+    let image = screenshot();
+    let decoded =
+        ImageData::decode(&image.encode(ImageFormat::Jpeg).unwrap(), ImageFormat::Jpeg).unwrap();
+
+    assert_eq!((4, 3), (decoded.width, decoded.height));
+    // JPEG has no alpha channel
+    assert!(decoded.rgba.chunks_exact(4).all(|pixel| pixel[3] == 255));
+}
+
+#[test]
+fn test_invalid_size() {
+    assert!(matches!(
+        ImageData::new(2, 2, vec![0; 15]),
+        Err(ClipboardError::ConversionFailed(_))
+    ));
+
+    let image = ImageData {
+        width: 3,
+        ..screenshot()
+    };
+    assert!(image.encode(ImageFormat::Png).is_err());
+}
+
+#[test]
+fn test_set_image() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    clipboard
+        .set_item(ClipboardItem::Image(screenshot()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Image(screenshot())),
+        other.get_format(&FormatKind::Image).unwrap()
+    );
+
+    // Snapshots store the image the way a MIME-based clipboard would, as a PNG
+    let snapshot = other.snapshot().unwrap();
+    let png = &snapshot.entries[0].data;
+    assert_eq!(
+        screenshot(),
+        ImageData::decode(png, ImageFormat::Png).unwrap()
+    );
+}
@@ -1,24 +1,48 @@
-use libclipboard::{Clipboard, ClipboardItem};
+use libclipboard::{Clipboard, ClipboardItem, FormatKind};
+
+fn clipboard() -> Clipboard {
+    Clipboard::new().unwrap_or_else(|_| Clipboard::in_memory())
+}
 
 #[test]
 fn test_set_text() {
-    let mut clipboard = Clipboard::new().unwrap();
+    let mut clipboard = clipboard();
 
-    clipboard.set_item(ClipboardItem::UnicodeText("".to_owned()));
+    clipboard
+        .set_item(ClipboardItem::UnicodeText("".to_owned()))
+        .unwrap();
     assert_eq!(
         ClipboardItem::UnicodeText("".to_string()),
-        clipboard.get_item().unwrap()
+        clipboard.get_item().unwrap().unwrap()
     );
 
-    clipboard.set_item(ClipboardItem::UnicodeText("Hello World!".to_owned()));
+    clipboard
+        .set_item(ClipboardItem::UnicodeText("Hello World!".to_owned()))
+        .unwrap();
     assert_eq!(
         ClipboardItem::UnicodeText("Hello World!".to_string()),
-        clipboard.get_item().unwrap()
+        clipboard.get_item().unwrap().unwrap()
     );
 
-    clipboard.set_item(ClipboardItem::UnicodeText("Goodbye World!".to_owned()));
+    clipboard
+        .set_item(ClipboardItem::UnicodeText("Goodbye World!".to_owned()))
+        .unwrap();
     assert_eq!(
         ClipboardItem::UnicodeText("Goodbye World!".to_string()),
-        clipboard.get_item().unwrap()
+        clipboard.get_item().unwrap().unwrap()
+    );
+}
+
+#[test]
+fn test_set_file_path() {
+    let mut clipboard = clipboard();
+
+    // Written as a percent-encoded file URL where the platform expects one
+    clipboard
+        .set_item(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned())),
+        clipboard.get_format(&FormatKind::FileList).unwrap()
     );
 }
@@ -0,0 +1,401 @@
+use std::{cell::RefCell, io::Cursor};
+
+use libclipboard::{
+    memory::MemoryClipboard, BackendFactory, Clipboard, ClipboardBackend, ClipboardError,
+    ClipboardItem, ClipboardKind, ClipboardSnapshot, FormatInfo, FormatKind, ImageData,
+    NativeFormat, SetOptions, WriteOptions,
+};
+
+#[test]
+fn test_handles_share_contents() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    clipboard
+        .set_item(ClipboardItem::Text("Hello World!".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Text("Hello World!".to_owned())),
+        other.get_item().unwrap()
+    );
+    assert_eq!(1, other.available_formats().unwrap().len());
+
+    clipboard.clear().unwrap();
+    assert_eq!(None, other.get_item().unwrap());
+    assert_eq!(0, other.available_formats().unwrap().len());
+}
+
+#[test]
+fn test_change_tracking_is_per_handle() {
+    let backend = MemoryClipboard::new();
+    let first = Clipboard::from_backend(backend.new_handle());
+    let second = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    assert!(!first.has_changed());
+
+    clipboard
+        .set_item(ClipboardItem::Text("Hello World!".to_owned()))
+        .unwrap();
+    assert!(first.has_changed());
+    assert!(!first.has_changed());
+    assert!(second.has_changed());
+}
+
+#[test]
+fn test_change_count() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+
+    let seen = other.change_count().unwrap();
+    assert_eq!(seen, clipboard.change_count().unwrap());
+
+    clipboard
+        .set_item(ClipboardItem::Text("Hello World!".to_owned()))
+        .unwrap();
+    clipboard.clear().unwrap();
+
+    // Reading the count doesn't consume the change for anyone else
+    assert!(other.change_count().unwrap() > seen);
+    assert_eq!(
+        other.change_count().unwrap(),
+        clipboard.change_count().unwrap()
+    );
+    assert!(other.has_changed());
+}
+
+#[test]
+fn test_ownership() {
+    let backend = MemoryClipboard::new();
+    let mut other = backend.new_handle();
+    let mut clipboard = backend;
+
+    assert!(!clipboard.is_owner());
+
+    clipboard
+        .set_item(ClipboardItem::Text("A".to_owned()))
+        .unwrap();
+    assert!(clipboard.is_owner());
+    assert!(!other.is_owner());
+
+    other.set_item(ClipboardItem::Text("B".to_owned())).unwrap();
+    assert!(!clipboard.is_owner());
+    assert!(other.is_owner());
+}
+
+#[test]
+fn test_clear_reports_ownership() {
+    let backend = MemoryClipboard::new();
+    let mut other = backend.new_handle();
+    let mut clipboard = backend;
+
+    clipboard
+        .set_item(ClipboardItem::Text("A".to_owned()))
+        .unwrap();
+    assert!(other.has_changed());
+
+    assert!(!other.clear().unwrap());
+    assert!(clipboard.has_changed());
+    assert!(other.has_changed());
+    assert_eq!(None, clipboard.get_item().unwrap());
+
+    clipboard
+        .set_item(ClipboardItem::Text("B".to_owned()))
+        .unwrap();
+    assert!(clipboard.clear().unwrap());
+    assert!(!clipboard.clear().unwrap());
+}
+
+#[test]
+fn test_set_multiple_formats() {
+    let mut clipboard = Clipboard::in_memory();
+
+    clipboard
+        .set_items(&[
+            ClipboardItem::Html("<b>Hello World!</b>".to_owned()),
+            ClipboardItem::UnicodeText("Hello World!".to_owned()),
+            ClipboardItem::UnicodeText("Ignored".to_owned()),
+        ])
+        .unwrap();
+
+    assert_eq!(2, clipboard.available_formats().unwrap().len());
+    assert_eq!(
+        vec![
+            ClipboardItem::Html("<b>Hello World!</b>".to_owned()),
+            ClipboardItem::UnicodeText("Hello World!".to_owned()),
+        ],
+        clipboard.get_items().unwrap()
+    );
+}
+
+#[test]
+fn test_available_formats() {
+    let mut clipboard = Clipboard::in_memory();
+
+    clipboard
+        .set_items(&[
+            ClipboardItem::Html("<b>Hi</b>".to_owned()),
+            ClipboardItem::UnicodeText("Hi".to_owned()),
+        ])
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            FormatInfo {
+                kind: FormatKind::Html,
+                native: NativeFormat::MimeType("text/html".to_owned()),
+                size: Some(9),
+            },
+            FormatInfo {
+                kind: FormatKind::UnicodeText,
+                native: NativeFormat::MimeType("text/plain;charset=utf-8".to_owned()),
+                size: Some(2),
+            },
+        ],
+        clipboard.available_formats().unwrap()
+    );
+}
+
+#[test]
+fn test_get_preferred() {
+    let mut clipboard = Clipboard::in_memory();
+
+    clipboard
+        .set_items(&[
+            ClipboardItem::UnicodeText("Hi".to_owned()),
+            ClipboardItem::Html("<b>Hi</b>".to_owned()),
+        ])
+        .unwrap();
+
+    assert_eq!(
+        Some(ClipboardItem::Html("<b>Hi</b>".to_owned())),
+        clipboard.get_preferred(&[]).unwrap()
+    );
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Hi".to_owned())),
+        clipboard
+            .get_preferred(&[FormatKind::Rtf, FormatKind::UnicodeText])
+            .unwrap()
+    );
+    assert_eq!(None, clipboard.get_preferred(&[FormatKind::Png]).unwrap());
+    assert_eq!(
+        Some(ClipboardItem::Html("<b>Hi</b>".to_owned())),
+        clipboard.get_format(&FormatKind::Html).unwrap()
+    );
+}
+
+#[test]
+fn test_kind() {
+    assert_eq!(ClipboardKind::Clipboard, Clipboard::in_memory().kind());
+    assert_eq!(
+        "the primary selection is not supported on this platform",
+        ClipboardError::UnsupportedKind(ClipboardKind::Primary).to_string()
+    );
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let mut clipboard = Clipboard::in_memory();
+    let items = vec![
+        ClipboardItem::Html("<b>Hi</b>".to_owned()),
+        ClipboardItem::FilePath("/tmp/hi.txt".to_owned()),
+        ClipboardItem::Url("file:///tmp/hi.txt".to_owned()),
+        ClipboardItem::Png(Cursor::new(vec![0x89, b'P', b'N', b'G'])),
+        ClipboardItem::Custom {
+            format: "com.example.snapshot".to_owned(),
+            data: vec![1, 2, 3],
+        },
+    ];
+
+    // Without fallbacks, so the restored items can be compared with the ones written
+    clipboard
+        .set_items_with_write_options(
+            &items,
+            &SetOptions::default(),
+            &WriteOptions { fallbacks: false },
+        )
+        .unwrap();
+    let snapshot = clipboard.snapshot().unwrap();
+    assert_eq!(5, snapshot.entries.len());
+
+    clipboard
+        .set_item(ClipboardItem::Text("Injected".to_owned()))
+        .unwrap();
+    clipboard.restore(&snapshot).unwrap();
+
+    assert_eq!(items, clipboard.get_items().unwrap());
+}
+
+#[test]
+fn test_snapshot_and_restore_images() {
+    let mut clipboard = Clipboard::in_memory();
+    let items = vec![
+        ClipboardItem::Image(ImageData::new(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 128]).unwrap()),
+        ClipboardItem::Png(Cursor::new(vec![0x89, b'P', b'N', b'G'])),
+    ];
+
+    clipboard
+        .set_items_with_write_options(
+            &items,
+            &SetOptions::default(),
+            &WriteOptions { fallbacks: false },
+        )
+        .unwrap();
+    let snapshot = clipboard.snapshot().unwrap();
+
+    clipboard.clear().unwrap();
+    clipboard.restore(&snapshot).unwrap();
+
+    assert_eq!(items, clipboard.get_items().unwrap());
+}
+
+#[test]
+fn test_set_options() {
+    let backend = MemoryClipboard::new();
+    let other = Clipboard::from_backend(backend.new_handle());
+    let mut clipboard = Clipboard::from_backend(backend);
+    let options = SetOptions {
+        concealed: true,
+        exclude_from_history: true,
+        ..Default::default()
+    };
+
+    clipboard
+        .set_item_with_options(ClipboardItem::UnicodeText("hunter2".to_owned()), &options)
+        .unwrap();
+    assert_eq!(
+        Some((ClipboardItem::UnicodeText("hunter2".to_owned()), options)),
+        other.get_item_with_options().unwrap()
+    );
+
+    // The options survive a snapshot as the KDE password manager hint
+    let snapshot = clipboard.snapshot().unwrap();
+    assert!(snapshot.entries.iter().any(
+        |entry| entry.native == NativeFormat::MimeType("x-kde-passwordManagerHint".to_owned())
+    ));
+    clipboard
+        .set_item(ClipboardItem::Text("Public".to_owned()))
+        .unwrap();
+    assert_eq!(SetOptions::default(), other.options().unwrap());
+    assert_eq!(
+        (
+            vec![ClipboardItem::Text("Public".to_owned())],
+            SetOptions::default()
+        ),
+        other.get_items_with_options().unwrap()
+    );
+
+    clipboard.restore(&snapshot).unwrap();
+    assert_eq!(options, other.options().unwrap());
+    assert_eq!(1, other.get_items().unwrap().len());
+}
+
+#[test]
+fn test_snapshot_and_restore_transient() {
+    let mut clipboard = Clipboard::in_memory();
+    let options = SetOptions {
+        transient: true,
+        ..Default::default()
+    };
+
+    clipboard
+        .set_item_with_options(ClipboardItem::UnicodeText("Pasting".to_owned()), &options)
+        .unwrap();
+    let snapshot = clipboard.snapshot().unwrap();
+
+    clipboard.clear().unwrap();
+    clipboard.restore(&snapshot).unwrap();
+
+    assert!(clipboard.options().unwrap().transient);
+    assert_eq!(options, clipboard.options().unwrap());
+    assert_eq!(1, clipboard.get_items().unwrap().len());
+}
+
+/// A backend whose contents are replaced by another application while its options are read.
+#[derive(Debug)]
+struct Racing {
+    inner: MemoryClipboard,
+    other: RefCell<Option<MemoryClipboard>>,
+}
+
+impl ClipboardBackend for Racing {
+    fn get_item(&self) -> Result<Option<ClipboardItem>, ClipboardError> {
+        self.inner.get_item()
+    }
+
+    fn get_items(&self) -> Result<Vec<ClipboardItem>, ClipboardError> {
+        self.inner.get_items()
+    }
+
+    fn set_items_with_options(
+        &mut self,
+        items: &[ClipboardItem],
+        options: &SetOptions,
+    ) -> Result<(), ClipboardError> {
+        self.inner.set_items_with_options(items, options)
+    }
+
+    fn options(&self) -> Result<SetOptions, ClipboardError> {
+        if let Some(mut other) = self.other.borrow_mut().take() {
+            other.set_item(ClipboardItem::Text("Public".to_owned()))?;
+        }
+
+        self.inner.options()
+    }
+
+    fn clear(&mut self) -> Result<bool, ClipboardError> {
+        self.inner.clear()
+    }
+
+    fn available_formats(&self) -> Result<Vec<FormatInfo>, ClipboardError> {
+        self.inner.available_formats()
+    }
+
+    fn snapshot(&self) -> Result<ClipboardSnapshot, ClipboardError> {
+        self.inner.snapshot()
+    }
+
+    fn restore(&mut self, snapshot: &ClipboardSnapshot) -> Result<(), ClipboardError> {
+        self.inner.restore(snapshot)
+    }
+
+    fn change_count(&self) -> Result<u64, ClipboardError> {
+        self.inner.change_count()
+    }
+
+    fn has_changed(&self) -> bool {
+        self.inner.has_changed()
+    }
+
+    fn factory(&self) -> BackendFactory {
+        self.inner.factory()
+    }
+}
+
+#[test]
+fn test_items_and_options_describe_the_same_contents() {
+    let inner = MemoryClipboard::new();
+    let other = RefCell::new(Some(inner.new_handle()));
+    let mut clipboard = Clipboard::from_backend(Racing { inner, other });
+
+    clipboard
+        .set_item_with_options(
+            ClipboardItem::Text("hunter2".to_owned()),
+            &SetOptions {
+                concealed: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    // The public text must not be reported as concealed, nor the password as public
+    assert_eq!(
+        (
+            vec![ClipboardItem::Text("Public".to_owned())],
+            SetOptions::default()
+        ),
+        clipboard.get_items_with_options().unwrap()
+    );
+}
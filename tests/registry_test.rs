@@ -0,0 +1,103 @@
+use libclipboard::{FormatIdentifiers, FormatKind, WindowsFormat};
+
+#[test]
+fn test_text_aliases() {
+    for mime_type in ["text/plain;charset=utf-8", "text/plain; charset=UTF-8"] {
+        assert_eq!(
+            Some(FormatKind::UnicodeText),
+            FormatKind::from_mime_type(mime_type)
+        );
+    }
+    assert_eq!(
+        Some(FormatKind::Text),
+        FormatKind::from_mime_type("text/plain")
+    );
+
+    assert_eq!(
+        Some(FormatKind::UnicodeText),
+        FormatKind::from_uti("public.utf8-plain-text")
+    );
+    assert_eq!(
+        Some(FormatKind::UnicodeText),
+        FormatKind::from_x11_target("UTF8_STRING")
+    );
+    assert_eq!(
+        Some(FormatKind::Text),
+        FormatKind::from_x11_target("STRING")
+    );
+    assert_eq!(
+        Some(FormatKind::UnicodeText),
+        FormatKind::from_windows_format(13, None)
+    );
+}
+
+#[test]
+fn test_registered_windows_formats() {
+    assert_eq!(
+        Some(FormatKind::Html),
+        FormatKind::from_windows_format(0xC0A1, Some("HTML Format"))
+    );
+    assert_eq!(
+        Some(FormatKind::Png),
+        FormatKind::from_windows_format(0xC0A2, Some("PNG"))
+    );
+    assert_eq!(
+        None,
+        FormatKind::from_windows_format(0xC0A3, Some("Unknown"))
+    );
+    assert_eq!(
+        Some(FormatKind::Image),
+        FormatKind::from_windows_format(8, None)
+    );
+}
+
+#[test]
+fn test_shared_identifiers_resolve_to_the_first_kind() {
+    assert_eq!(
+        Some(FormatKind::Url),
+        FormatKind::from_mime_type("text/uri-list")
+    );
+    assert_eq!(
+        Some(FormatKind::Png),
+        FormatKind::from_mime_type("image/png")
+    );
+    assert_eq!(Some(FormatKind::Png), FormatKind::from_uti("public.png"));
+}
+
+#[test]
+fn test_every_identifier_maps_back() {
+    for identifiers in FormatIdentifiers::all() {
+        let kind = &identifiers.kind;
+
+        assert_eq!(Some(identifiers), kind.identifiers());
+        assert_eq!(
+            identifiers.mime_types.first().map(|mime| mime.to_string()),
+            kind.mime_type()
+        );
+
+        for mime_type in identifiers.mime_types {
+            assert!(
+                FormatKind::from_mime_type(mime_type).is_some(),
+                "{mime_type}"
+            );
+        }
+        for uti in identifiers.utis {
+            assert!(FormatKind::from_uti(uti).is_some(), "{uti}");
+        }
+        for target in identifiers.x11_targets {
+            assert!(FormatKind::from_x11_target(target).is_some(), "{target}");
+        }
+        for format in identifiers.windows_formats {
+            let found = match *format {
+                WindowsFormat::Standard { id, .. } => FormatKind::from_windows_format(id, None),
+                WindowsFormat::Registered(name) => {
+                    FormatKind::from_windows_format(0xC000, Some(name))
+                }
+            };
+            assert!(found.is_some(), "{format:?}");
+        }
+    }
+
+    assert_eq!(None, FormatKind::Unknown.identifiers());
+    assert_eq!(None, FormatKind::Custom("x".to_owned()).identifiers());
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "serde")]
+
+use std::io::Cursor;
+
+use libclipboard::{
+    memory::MemoryClipboard, Clipboard, ClipboardItem, ClipboardSnapshot, FormatKind, ImageData,
+    NativeFormat, SetOptions,
+};
+
+fn items() -> Vec<ClipboardItem> {
+    vec![
+        ClipboardItem::UnicodeText("Hello World! 👋".to_owned()),
+        ClipboardItem::Html("<b>Hello</b>".to_owned()),
+        ClipboardItem::FilePath("/tmp/hello.txt".to_owned()),
+        ClipboardItem::Png(Cursor::new(vec![0x89, b'P', b'N', b'G', 0, 255])),
+        ClipboardItem::Pdf(Cursor::new(b"%PDF-1.7".to_vec())),
+        ClipboardItem::Image(ImageData {
+            dpi: Some(72),
+            ..ImageData::new(1, 2, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap()
+        }),
+        ClipboardItem::RawBytes(vec![-128, -1, 0, 127]),
+        ClipboardItem::Custom {
+            format: "com.example.app.layer".to_owned(),
+            data: vec![0, 1, 2, 253, 254, 255],
+        },
+    ]
+}
+
+fn snapshot() -> ClipboardSnapshot {
+    let mut clipboard = Clipboard::from_backend(MemoryClipboard::new());
+
+    clipboard
+        .set_items_with_options(
+            &items(),
+            &SetOptions {
+                concealed: true,
+                ..SetOptions::default()
+            },
+        )
+        .unwrap();
+
+    clipboard.snapshot().unwrap()
+}
+
+#[test]
+fn test_json_round_trip() {
+    let json = serde_json::to_string(&items()).unwrap();
+    assert_eq!(
+        items(),
+        serde_json::from_str::<Vec<ClipboardItem>>(&json).unwrap()
+    );
+
+    let json = serde_json::to_string(&snapshot()).unwrap();
+    assert_eq!(
+        snapshot(),
+        serde_json::from_str::<ClipboardSnapshot>(&json).unwrap()
+    );
+}
+
+#[test]
+fn test_json_uses_base64() {
+    let json = serde_json::to_value(ClipboardItem::Pdf(Cursor::new(b"%PDF-1.7".to_vec()))).unwrap();
+    assert_eq!(serde_json::json!({ "Pdf": "JVBERi0xLjc=" }), json);
+
+    let json = serde_json::to_value(ClipboardItem::RawBytes(vec![-1, 0])).unwrap();
+    assert_eq!(serde_json::json!({ "RawBytes": "/wA=" }), json);
+}
+
+#[test]
+fn test_bincode_round_trip() {
+    let bytes = bincode::serialize(&items()).unwrap();
+    assert_eq!(
+        items(),
+        bincode::deserialize::<Vec<ClipboardItem>>(&bytes).unwrap()
+    );
+
+    let bytes = bincode::serialize(&snapshot()).unwrap();
+    assert_eq!(
+        snapshot(),
+        bincode::deserialize::<ClipboardSnapshot>(&bytes).unwrap()
+    );
+}
+
+#[test]
+fn test_bincode_stores_raw_bytes() {
+    let data = vec![7; 1000];
+    let bytes = bincode::serialize(&ClipboardItem::Custom {
+        format: "a".to_owned(),
+        data,
+    })
+    .unwrap();
+
+    // Variant index, format name and length prefixes around the payload, with no per-byte overhead
+    assert!(bytes.len() < 1000 + 32);
+}
+
+#[test]
+fn test_format_descriptors() {
+    let kinds = vec![
+        FormatKind::Html,
+        FormatKind::Image,
+        FormatKind::Custom("com.example.app.layer".to_owned()),
+        FormatKind::Unknown,
+    ];
+    let json = serde_json::to_string(&kinds).unwrap();
+    assert_eq!(
+        kinds,
+        serde_json::from_str::<Vec<FormatKind>>(&json).unwrap()
+    );
+
+    let native = NativeFormat::Windows {
+        id: 49_161,
+        name: Some("HTML Format".to_owned()),
+    };
+    let bytes = bincode::serialize(&native).unwrap();
+    assert_eq!(native, bincode::deserialize(&bytes).unwrap());
+
+    let mut clipboard = Clipboard::from_backend(MemoryClipboard::new());
+    clipboard.set_items(&items()).unwrap();
+
+    let formats = clipboard.available_formats().unwrap();
+    let json = serde_json::to_string(&formats).unwrap();
+    assert_eq!(formats, serde_json::from_str::<Vec<_>>(&json).unwrap());
+}
@@ -0,0 +1,89 @@
+use std::{
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
+};
+
+use libclipboard::{memory::MemoryClipboard, Clipboard, ClipboardItem, ClipboardKind};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn test_subscribers_receive_changes() {
+    let backend = MemoryClipboard::new();
+    let mut writer = Clipboard::from_backend(backend.new_handle());
+    let clipboard = Clipboard::from_backend(backend);
+
+    let watcher = clipboard.watch().unwrap();
+    let first = watcher.subscribe();
+    let second = watcher.subscribe();
+
+    let (callback_tx, callback_rx) = mpsc::channel();
+    watcher.on_change(move |change| callback_tx.send(change.clone()).unwrap());
+
+    writer
+        .set_item(ClipboardItem::Text("Hello World!".to_owned()))
+        .unwrap();
+
+    let change = first.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(ClipboardKind::Clipboard, change.kind);
+    assert_eq!(change.kind, second.recv_timeout(TIMEOUT).unwrap().kind);
+    assert_eq!(change.kind, callback_rx.recv_timeout(TIMEOUT).unwrap().kind);
+
+    // The watcher doesn't consume the changes seen by the clipboard itself
+    assert!(clipboard.has_changed());
+}
+
+#[test]
+fn test_callbacks_can_subscribe() {
+    let backend = MemoryClipboard::new();
+    let mut writer = Clipboard::from_backend(backend.new_handle());
+    let clipboard = Clipboard::from_backend(backend);
+
+    let watcher = Arc::new(clipboard.watch().unwrap());
+    let weak = Arc::downgrade(&watcher);
+    let (subscribed_tx, subscribed_rx) = mpsc::channel();
+    let mut subscribed_tx = Some(subscribed_tx);
+    watcher.on_change(move |_| {
+        if let (Some(tx), Some(watcher)) = (subscribed_tx.take(), weak.upgrade()) {
+            tx.send(watcher.subscribe()).unwrap();
+        }
+    });
+
+    writer
+        .set_item(ClipboardItem::Text("First".to_owned()))
+        .unwrap();
+    let later = subscribed_rx.recv_timeout(TIMEOUT).unwrap();
+
+    writer
+        .set_item(ClipboardItem::Text("Second".to_owned()))
+        .unwrap();
+    assert!(later.recv_timeout(TIMEOUT).is_ok());
+}
+
+#[test]
+fn test_watchers_are_independent() {
+    let mut clipboard = Clipboard::in_memory();
+    let first = clipboard.watch().unwrap();
+    let second = clipboard.watch().unwrap();
+    let first_changes = first.subscribe();
+    let second_changes = second.subscribe();
+
+    clipboard.clear().unwrap();
+
+    assert!(first_changes.recv_timeout(TIMEOUT).is_ok());
+    assert!(second_changes.recv_timeout(TIMEOUT).is_ok());
+}
+
+#[test]
+fn test_stop() {
+    let mut clipboard = Clipboard::in_memory();
+    let watcher = clipboard.watch().unwrap();
+    let changes = watcher.subscribe();
+
+    let start = Instant::now();
+    watcher.stop();
+    assert!(start.elapsed() < TIMEOUT);
+
+    clipboard.clear().unwrap();
+    assert!(changes.recv_timeout(Duration::from_millis(200)).is_err());
+}
@@ -0,0 +1,323 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    os::{fd::AsFd, unix::net::UnixStream},
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use libclipboard::{
+    wayland::WaylandCC, Clipboard, ClipboardError, ClipboardItem, ClipboardKind, FormatKind,
+    NativeFormat, SetOptions,
+};
+use wayland_client::{
+    delegate_noop, event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+// The tests share the selection of the seat, so they take turns
+static SEAT: Mutex<()> = Mutex::new(());
+
+fn seat() -> MutexGuard<'static, ()> {
+    SEAT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn clipboard() -> Clipboard {
+    Clipboard::from_backend(WaylandCC::new().unwrap())
+}
+
+fn primary() -> Clipboard {
+    Clipboard::from_backend(WaylandCC::with_kind(ClipboardKind::Primary).unwrap())
+}
+
+#[test]
+#[ignore = "needs a Wayland compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_round_trip() {
+    let _seat = seat();
+    let mut writer = clipboard();
+    let reader = clipboard();
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Hello World! 👋".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Hello World! 👋".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+
+    writer
+        .set_item(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned())),
+        reader.get_format(&FormatKind::FileList).unwrap()
+    );
+
+    let png = vec![0x89, b'P', b'N', b'G', 0, 255];
+    writer
+        .set_item(ClipboardItem::Png(Cursor::new(png.clone())))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Png(Cursor::new(png))),
+        reader.get_format(&FormatKind::Png).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs a Wayland compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_mime_types() {
+    let _seat = seat();
+    let mut writer = clipboard();
+    let reader = clipboard();
+
+    writer
+        .set_items_with_options(
+            &[
+                ClipboardItem::Html("<b>Hi</b>".to_owned()),
+                ClipboardItem::UnicodeText("Hi".to_owned()),
+            ],
+            &SetOptions {
+                concealed: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let natives: Vec<_> = reader
+        .available_formats()
+        .unwrap()
+        .into_iter()
+        .map(|format| format.native)
+        .collect();
+    for mime_type in [
+        "text/html",
+        "text/plain;charset=utf-8",
+        "x-kde-passwordManagerHint",
+    ] {
+        assert!(
+            natives.contains(&NativeFormat::MimeType(mime_type.to_owned())),
+            "{mime_type} in {natives:?}"
+        );
+    }
+
+    assert_eq!(
+        vec![
+            ClipboardItem::Html("<b>Hi</b>".to_owned()),
+            ClipboardItem::UnicodeText("Hi".to_owned()),
+        ],
+        reader.get_items().unwrap()
+    );
+    assert!(reader.options().unwrap().concealed);
+}
+
+#[test]
+#[ignore = "needs a Wayland compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_clear_and_changes() {
+    let _seat = seat();
+    let mut writer = clipboard();
+    let reader = clipboard();
+    reader.has_changed();
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Copied".to_owned()))
+        .unwrap();
+    assert!(reader.has_changed());
+    assert!(!reader.has_changed());
+
+    let snapshot = reader.snapshot().unwrap();
+    assert!(writer.clear().unwrap());
+    assert!(reader.has_changed());
+    assert_eq!(None, reader.get_item().unwrap());
+    assert!(!writer.clear().unwrap());
+
+    writer.restore(&snapshot).unwrap();
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Copied".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs a Wayland compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_other_kinds_are_unsupported() {
+    let _seat = seat();
+    assert_eq!(
+        ClipboardError::UnsupportedKind(ClipboardKind::Secondary),
+        WaylandCC::with_kind(ClipboardKind::Secondary).unwrap_err()
+    );
+}
+
+#[test]
+#[ignore = "needs a Wayland compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_primary_selection() {
+    let _seat = seat();
+    let mut clipboard = clipboard();
+    let mut writer = primary();
+    let reader = primary();
+
+    clipboard
+        .set_item(ClipboardItem::UnicodeText("Copied".to_owned()))
+        .unwrap();
+    clipboard.has_changed();
+    reader.has_changed();
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Selected".to_owned()))
+        .unwrap();
+    assert!(reader.has_changed());
+    assert!(!clipboard.has_changed());
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Selected".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+
+    // The selections are independent of each other
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Copied".to_owned())),
+        clipboard.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+    assert!(writer.clear().unwrap());
+    assert!(reader.has_changed());
+    assert_eq!(None, reader.get_item().unwrap());
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Copied".to_owned())),
+        clipboard.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+}
+
+/// Another client, which sets or reads the selection without going through this crate.
+#[derive(Default)]
+struct Other {
+    /// The last selection the compositor announced.
+    selection: Option<ZwlrDataControlOfferV1>,
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Other {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(Other: ignore WlSeat);
+delegate_noop!(Other: ZwlrDataControlManagerV1);
+delegate_noop!(Other: ignore ZwlrDataControlOfferV1);
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for Other {
+    fn event(
+        other: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_device_v1::Event::Selection { id: Some(offer) } = event {
+            other.selection = Some(offer);
+        }
+    }
+
+    event_created_child!(Other, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, ()> for Other {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrDataControlSourceV1,
+        event: zwlr_data_control_source_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_data_control_source_v1::Event::Send { fd, .. } = event {
+            File::from(fd).write_all(b"From elsewhere").unwrap();
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs a wlroots-based compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_background_access() {
+    let _seat = seat();
+    let reader = clipboard();
+    let watcher = reader.watch().unwrap();
+    let changes = watcher.subscribe();
+    reader.has_changed();
+
+    // Another client without a surface or keyboard focus sets the selection
+    let conn = Connection::connect_to_env().unwrap();
+    let (globals, mut queue) = registry_queue_init::<Other>(&conn).unwrap();
+    let qh = queue.handle();
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).unwrap();
+    let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=1, ()).unwrap();
+    let device = manager.get_data_device(&seat, &qh, ());
+    let source = manager.create_data_source(&qh, ());
+    source.offer("text/plain;charset=utf-8".to_owned());
+    device.set_selection(Some(&source));
+    conn.flush().unwrap();
+    thread::spawn(move || {
+        let mut other = Other::default();
+        while queue.blocking_dispatch(&mut other).is_ok() {}
+    });
+
+    let change = changes.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(ClipboardKind::Clipboard, change.kind);
+    assert!(reader.has_changed());
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("From elsewhere".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs a wlroots-based compositor, e.g. WLR_BACKENDS=headless sway"]
+fn test_round_trip_through_compositor() {
+    let _seat = seat();
+    let mut writer = clipboard();
+
+    // Another client reads the selection, so the data goes through the compositor
+    let conn = Connection::connect_to_env().unwrap();
+    let (globals, mut queue) = registry_queue_init::<Other>(&conn).unwrap();
+    let qh = queue.handle();
+    let seat: WlSeat = globals.bind(&qh, 1..=1, ()).unwrap();
+    let manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=1, ()).unwrap();
+    let _device = manager.get_data_device(&seat, &qh, ());
+    let mut other = Other::default();
+    queue.roundtrip(&mut other).unwrap();
+    other.selection = None;
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Sent over the wire".to_owned()))
+        .unwrap();
+    while other.selection.is_none() {
+        queue.blocking_dispatch(&mut other).unwrap();
+    }
+
+    let (mut read, write) = UnixStream::pair().unwrap();
+    let offer = other.selection.take().unwrap();
+    offer.receive("text/plain;charset=utf-8".to_owned(), write.as_fd());
+    conn.flush().unwrap();
+    drop(write);
+
+    let mut text = String::new();
+    read.read_to_string(&mut text).unwrap();
+    assert_eq!("Sent over the wire", text);
+}
@@ -0,0 +1,478 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::{
+    io::Cursor,
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use libclipboard::{
+    x11::X11CC, Clipboard, ClipboardError, ClipboardItem, ClipboardKind, FormatKind, NativeFormat,
+    SetOptions,
+};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, ConnectionExt, CreateWindowAux, EventMask, PropMode,
+            SelectionNotifyEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+// The tests share the selections of the display, so they take turns
+static DISPLAY: Mutex<()> = Mutex::new(());
+
+fn display() -> MutexGuard<'static, ()> {
+    DISPLAY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn clipboard(kind: ClipboardKind) -> Clipboard {
+    Clipboard::from_backend(X11CC::with_kind(kind).unwrap())
+}
+
+/// Connects another client, which uses the selections without going through this crate.
+fn other_client() -> (RustConnection, Window) {
+    let (conn, screen) = x11rb::connect(None).unwrap();
+    let window = conn.generate_id().unwrap();
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        conn.setup().roots[screen].root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .unwrap();
+
+    (conn, window)
+}
+
+fn atom(conn: &RustConnection, name: &str) -> Atom {
+    conn.intern_atom(false, name.as_bytes())
+        .unwrap()
+        .reply()
+        .unwrap()
+        .atom
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_text_round_trip() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Clipboard);
+    let reader = clipboard(ClipboardKind::Clipboard);
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Hello World! 👋".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Hello World! 👋".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+
+    writer
+        .set_item(ClipboardItem::Text("Café".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Text("Café".to_owned())),
+        reader.get_format(&FormatKind::Text).unwrap()
+    );
+
+    // Latin-1 that happens to be valid UTF-8 as well
+    writer
+        .set_item(ClipboardItem::Text("Ã©".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Text("Ã©".to_owned())),
+        reader.get_format(&FormatKind::Text).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_targets() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Secondary);
+    let reader = clipboard(ClipboardKind::Secondary);
+
+    writer
+        .set_items_with_options(
+            &[
+                ClipboardItem::Html("<b>Hi</b>".to_owned()),
+                ClipboardItem::UnicodeText("Hi".to_owned()),
+            ],
+            &SetOptions {
+                concealed: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let natives: Vec<_> = reader
+        .available_formats()
+        .unwrap()
+        .into_iter()
+        .map(|format| format.native)
+        .collect();
+    for target in ["text/html", "UTF8_STRING", "x-kde-passwordManagerHint"] {
+        assert!(
+            natives.contains(&NativeFormat::X11Target(target.to_owned())),
+            "{target} in {natives:?}"
+        );
+    }
+
+    assert_eq!(
+        vec![
+            ClipboardItem::Html("<b>Hi</b>".to_owned()),
+            ClipboardItem::UnicodeText("Hi".to_owned()),
+        ],
+        reader.get_items().unwrap()
+    );
+    assert!(reader.options().unwrap().concealed);
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_files_urls_and_images() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Primary);
+    let reader = clipboard(ClipboardKind::Primary);
+
+    writer
+        .set_item(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::FilePath("/tmp/hello world.txt".to_owned())),
+        reader.get_format(&FormatKind::FileList).unwrap()
+    );
+
+    writer
+        .set_item(ClipboardItem::Url("https://example.com".to_owned()))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Url("https://example.com".to_owned())),
+        reader.get_format(&FormatKind::Url).unwrap()
+    );
+
+    let png = vec![0x89, b'P', b'N', b'G', 0, 255];
+    writer
+        .set_item(ClipboardItem::Png(Cursor::new(png.clone())))
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Png(Cursor::new(png))),
+        reader.get_format(&FormatKind::Png).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_clear_and_changes() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Primary);
+    let reader = clipboard(ClipboardKind::Primary);
+
+    writer
+        .set_item(ClipboardItem::UnicodeText("Selected".to_owned()))
+        .unwrap();
+    assert!(reader.has_changed());
+    assert!(!reader.has_changed());
+
+    let snapshot = reader.snapshot().unwrap();
+    assert!(writer.clear().unwrap());
+    assert!(reader.has_changed());
+    assert_eq!(None, reader.get_item().unwrap());
+    assert!(!writer.clear().unwrap());
+
+    writer.restore(&snapshot).unwrap();
+    assert_eq!(
+        Some(ClipboardItem::UnicodeText("Selected".to_owned())),
+        reader.get_format(&FormatKind::UnicodeText).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_pasteboards_are_unsupported() {
+    let _display = display();
+    assert_eq!(
+        ClipboardError::UnsupportedKind(ClipboardKind::Find),
+        X11CC::with_kind(ClipboardKind::Find).unwrap_err()
+    );
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_large_transfers() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Clipboard);
+    let reader = clipboard(ClipboardKind::Clipboard);
+
+    // Larger than the default maximum request size of Xvfb, and than any reduced one
+    let data: Vec<u8> = (0..40 << 20).map(|i| (i % 251) as u8).collect();
+
+    writer
+        .set_items(&[
+            ClipboardItem::Png(Cursor::new(data.clone())),
+            ClipboardItem::Pdf(Cursor::new(data[..3 << 20].to_vec())),
+        ])
+        .unwrap();
+    assert_eq!(
+        Some(ClipboardItem::Png(Cursor::new(data.clone()))),
+        reader.get_format(&FormatKind::Png).unwrap()
+    );
+    assert_eq!(
+        Some(ClipboardItem::Pdf(Cursor::new(data[..3 << 20].to_vec()))),
+        reader.get_format(&FormatKind::Pdf).unwrap()
+    );
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_other_owners_are_reported() {
+    let _display = display();
+    let reader = clipboard(ClipboardKind::Clipboard);
+    let watcher = reader.watch().unwrap();
+    let changes = watcher.subscribe();
+    let change_count = reader.change_count().unwrap();
+    reader.has_changed();
+
+    // Another client takes over the clipboard without writing through this crate
+    let (conn, window) = other_client();
+    let clipboard = atom(&conn, "CLIPBOARD");
+    conn.set_selection_owner(window, clipboard, CURRENT_TIME)
+        .unwrap();
+    conn.flush().unwrap();
+
+    let change = changes.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(ClipboardKind::Clipboard, change.kind);
+    assert_eq!(Some(u64::from(window)), change.native.unwrap().owner);
+
+    assert!(reader.has_changed());
+    assert!(reader.change_count().unwrap() > change_count);
+
+    // The owner disconnecting is a change too
+    drop(conn);
+    let change = changes.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(None, change.native.unwrap().owner);
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_late_clears_are_ignored() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Clipboard);
+    let reader = clipboard(ClipboardKind::Clipboard);
+
+    let (conn, window) = other_client();
+    let clipboard = atom(&conn, "CLIPBOARD");
+
+    // The clear caused by the other client may only arrive after this process took over again
+    for i in 0..20 {
+        writer
+            .set_item(ClipboardItem::UnicodeText(format!("First {i}")))
+            .unwrap();
+        conn.set_selection_owner(window, clipboard, CURRENT_TIME)
+            .unwrap();
+        let owner = conn.get_selection_owner(clipboard).unwrap();
+        assert_eq!(window, owner.reply().unwrap().owner);
+        writer
+            .set_item(ClipboardItem::UnicodeText(format!("Second {i}")))
+            .unwrap();
+
+        assert_eq!(
+            Some(ClipboardItem::UnicodeText(format!("Second {i}"))),
+            reader.get_format(&FormatKind::UnicodeText).unwrap()
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_snapshot_keeps_property_types() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Clipboard);
+    let reader = clipboard(ClipboardKind::Clipboard);
+
+    // Another client answers `TEXT` in UTF-8 and a target with a list of atoms
+    let (owner, owner_window) = other_client();
+    let clipboard_atom = atom(&owner, "CLIPBOARD");
+    let targets = atom(&owner, "TARGETS");
+    let text = atom(&owner, "TEXT");
+    let utf8_string = atom(&owner, "UTF8_STRING");
+    let atoms = atom(&owner, "application/x-libclipboard-atoms");
+    let atom_list = [u32::from(AtomEnum::PRIMARY), u32::from(AtomEnum::SECONDARY)];
+
+    owner
+        .set_selection_owner(owner_window, clipboard_atom, CURRENT_TIME)
+        .unwrap();
+    owner.flush().unwrap();
+    let owner = thread::spawn(move || loop {
+        let request = match owner.wait_for_event().unwrap() {
+            Event::SelectionRequest(request) => request,
+            Event::SelectionClear(_) => break,
+            _ => continue,
+        };
+
+        if request.target == targets {
+            owner
+                .change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    request.property,
+                    AtomEnum::ATOM,
+                    &[targets, text, atoms],
+                )
+                .unwrap();
+        } else if request.target == text {
+            owner
+                .change_property8(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    request.property,
+                    utf8_string,
+                    "Café".as_bytes(),
+                )
+                .unwrap();
+        } else {
+            owner
+                .change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    request.property,
+                    AtomEnum::ATOM,
+                    &atom_list,
+                )
+                .unwrap();
+        }
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: request.property,
+        };
+        owner
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+            .unwrap();
+        owner.flush().unwrap();
+    });
+
+    let snapshot = reader.snapshot().unwrap();
+    writer.restore(&snapshot).unwrap();
+    owner.join().unwrap();
+
+    // The restored targets are answered exactly as the other client answered them
+    let (conn, window) = other_client();
+    let property = atom(&conn, "LIBCLIPBOARD_TEST");
+    for (target, property_type, format, data) in [
+        (text, utf8_string, 8, "Café".as_bytes().to_vec()),
+        (
+            atoms,
+            AtomEnum::ATOM.into(),
+            32,
+            atom_list
+                .iter()
+                .flat_map(|atom| atom.to_ne_bytes())
+                .collect(),
+        ),
+    ] {
+        conn.convert_selection(window, clipboard_atom, target, property, CURRENT_TIME)
+            .unwrap();
+        conn.flush().unwrap();
+        loop {
+            if let Event::SelectionNotify(event) = conn.wait_for_event().unwrap() {
+                assert_ne!(NONE, event.property);
+                break;
+            }
+        }
+
+        let reply = conn
+            .get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)
+            .unwrap()
+            .reply()
+            .unwrap();
+        assert_eq!(property_type, reply.type_);
+        assert_eq!(format, reply.format);
+        assert_eq!(data, reply.value);
+    }
+}
+
+#[test]
+#[ignore = "needs an X server, e.g. run with xvfb-run cargo test -- --ignored"]
+fn test_timestamp_and_multiple() {
+    let _display = display();
+    let mut writer = clipboard(ClipboardKind::Clipboard);
+    writer
+        .set_item(ClipboardItem::UnicodeText("Hi".to_owned()))
+        .unwrap();
+
+    let (conn, window) = other_client();
+    let clipboard_atom = atom(&conn, "CLIPBOARD");
+    let utf8_string = atom(&conn, "UTF8_STRING");
+    let unknown = atom(&conn, "application/x-libclipboard-unknown");
+    let property = atom(&conn, "LIBCLIPBOARD_TEST");
+    let second = atom(&conn, "LIBCLIPBOARD_TEST_SECOND");
+    let convert = |target: Atom| {
+        conn.convert_selection(window, clipboard_atom, target, property, CURRENT_TIME)
+            .unwrap();
+        conn.flush().unwrap();
+        loop {
+            if let Event::SelectionNotify(event) = conn.wait_for_event().unwrap() {
+                assert_ne!(NONE, event.property);
+                break;
+            }
+        }
+
+        conn.get_property(true, window, property, AtomEnum::ANY, 0, u32::MAX)
+            .unwrap()
+            .reply()
+            .unwrap()
+    };
+
+    let targets: Vec<Atom> = convert(atom(&conn, "TARGETS")).value32().unwrap().collect();
+    for target in ["TIMESTAMP", "MULTIPLE"] {
+        assert!(targets.contains(&atom(&conn, target)), "{target}");
+    }
+
+    let timestamp = convert(atom(&conn, "TIMESTAMP"));
+    assert_eq!(u32::from(AtomEnum::INTEGER), timestamp.type_);
+    assert_ne!(Some(0), timestamp.value32().unwrap().next());
+
+    // Targets that aren't offered have their property replaced with None
+    let atom_pair = atom(&conn, "ATOM_PAIR");
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        property,
+        atom_pair,
+        &[utf8_string, second, unknown, second],
+    )
+    .unwrap();
+    let pairs: Vec<Atom> = convert(atom(&conn, "MULTIPLE"))
+        .value32()
+        .unwrap()
+        .collect();
+    assert_eq!(vec![utf8_string, second, unknown, NONE], pairs);
+
+    let text = conn
+        .get_property(true, window, second, AtomEnum::ANY, 0, u32::MAX)
+        .unwrap()
+        .reply()
+        .unwrap();
+    assert_eq!(utf8_string, text.type_);
+    assert_eq!(b"Hi".to_vec(), text.value);
+}